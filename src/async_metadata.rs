@@ -0,0 +1,554 @@
+//!
+//! Reading and writing over `AsyncRead + AsyncSeek` sources. It is enabled with the `async`
+//! feature.
+//!
+//! Only the I/O is asynchronous. the units are read and written by `MetadataReader` and
+//! `MetadataWriter`, so the result is same with the sync API.
+//! The audio is not read into memory. the reader fetch only the bytes that `MetadataReader`
+//! read, e.g) the tags and the last 128 bytes, and the writer move the audio by chunks.
+//!
+extern crate futures;
+
+use self::futures::future::poll_fn;
+use self::futures::io::{AsyncRead, AsyncSeek, AsyncWrite};
+
+use error::{self, Id3Error};
+use metadata::{probe_reader_with, tag_bytes, MetadataReader, ParseOptions, TagRewrite, Unit};
+
+use std::cmp;
+use std::future::Future;
+use std::io::{self, ErrorKind, Read, Result, Seek, SeekFrom};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::vec::Vec;
+
+const BUF_SIZE: usize = 8 * 1024;
+
+///
+/// The bytes of a source that are fetched by `AsyncMetadataReader`.
+///
+/// It is `Read + Seek` with the length of the source. the bytes that are not fetched, e.g) the
+/// audio, are read as 0.
+///
+pub struct FetchedBytes {
+    len: u64,
+    position: u64,
+    //
+    // The fetched bytes and their offsets. they are sorted by the offset and do not overlap.
+    //
+    chunks: Vec<(u64, Vec<u8>)>,
+    //
+    // The ranges that are read as 0 because they are not fetched yet.
+    //
+    misses: Vec<(u64, u64)>,
+}
+
+impl FetchedBytes {
+    fn new(len: u64) -> Self {
+        FetchedBytes {
+            len: len,
+            position: 0,
+            chunks: Vec::new(),
+            misses: Vec::new(),
+        }
+    }
+
+    ///
+    /// The length of the source.
+    ///
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn insert(&mut self, offset: u64, bytes: Vec<u8>) {
+        let index = self.chunks
+            .iter()
+            .position(|chunk| chunk.0 > offset)
+            .unwrap_or(self.chunks.len());
+        self.chunks.insert(index, (offset, bytes));
+    }
+
+    //
+    // It return the missed ranges that are merged if they overlap or are adjacent.
+    //
+    fn take_misses(&mut self) -> Vec<(u64, u64)> {
+        let mut misses = ::std::mem::replace(&mut self.misses, Vec::new());
+        misses.sort();
+
+        let mut ret: Vec<(u64, u64)> = Vec::new();
+        for (start, end) in misses {
+            match ret.last_mut() {
+                Some(last) if start <= last.1 => last.1 = cmp::max(last.1, end),
+                _ => ret.push((start, end)),
+            }
+        }
+
+        ret
+    }
+}
+
+impl Read for FetchedBytes {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let start = self.position;
+        let end = cmp::min(start.saturating_add(buf.len() as u64), self.len);
+        if start >= end {
+            return Ok(0);
+        }
+
+        let amount = (end - start) as usize;
+        for b in buf[..amount].iter_mut() {
+            *b = 0;
+        }
+
+        let mut missed = start;
+        for &(offset, ref bytes) in &self.chunks {
+            let chunk_end = offset + bytes.len() as u64;
+            if chunk_end <= start || offset >= end {
+                continue;
+            }
+
+            let from = cmp::max(offset, start);
+            let to = cmp::min(chunk_end, end);
+            buf[(from - start) as usize..(to - start) as usize]
+                .copy_from_slice(&bytes[(from - offset) as usize..(to - offset) as usize]);
+
+            if from > missed {
+                self.misses.push((missed, from));
+            }
+            missed = to;
+        }
+
+        if missed < end {
+            self.misses.push((missed, end));
+        }
+
+        self.position = end;
+
+        Ok(amount)
+    }
+}
+
+impl Seek for FetchedBytes {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(n) => n as i64,
+            SeekFrom::End(n) => self.len as i64 + n,
+            SeekFrom::Current(n) => self.position as i64 + n,
+        };
+
+        if position < 0 {
+            return Err(io::Error::new(ErrorKind::InvalidInput,
+                                      "invalid seek to a negative position"));
+        }
+
+        self.position = position as u64;
+
+        Ok(self.position)
+    }
+}
+
+///
+/// Mp3 metadata reader over `AsyncRead + AsyncSeek`.
+///
+pub struct AsyncMetadataReader<R> {
+    readable: R,
+    options: ParseOptions,
+    frame_ids: Option<Vec<String>>,
+}
+
+impl<R> AsyncMetadataReader<R>
+    where R: AsyncRead + AsyncSeek + Unpin
+{
+    pub fn new(readable: R) -> Self {
+        AsyncMetadataReader {
+            readable: readable,
+            options: ParseOptions::default(),
+            frame_ids: None,
+        }
+    }
+
+    ///
+    /// Same with `MetadataReader::with_options`.
+    ///
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    ///
+    /// Same with `MetadataReader::with_frame_ids`.
+    ///
+    pub fn with_frame_ids(mut self, ids: &[&str]) -> Self {
+        self.frame_ids = Some(ids.iter().map(|id| id.to_string()).collect());
+        self
+    }
+
+    pub fn into_inner(self) -> R {
+        self.readable
+    }
+
+    ///
+    /// It fetch the bytes that `MetadataReader` read from the source, and return a
+    /// `MetadataReader` over the bytes.
+    ///
+    /// The header is read first, then the tag of the size in the header, and the last 128
+    /// bytes for the frame1. the stacked and the appended tags and the tags that a SEEK frame
+    /// point to are fetched too.
+    ///
+    pub fn read<'a>(&'a mut self)
+                    -> impl Future<Output = error::Result<MetadataReader<FetchedBytes>>> + 'a {
+        let mut fetch = Fetch::new();
+
+        poll_fn(move |cx| {
+            let options = &self.options;
+            let frame_ids = &self.frame_ids;
+
+            //
+            // The units are read until the end to know the bytes that are read. the frames are
+            // read lazily, so only the headers and the sizes are read, and the bodies are
+            // decoded once by the returned reader.
+            //
+            let lazy = ParseOptions { lazy: true, ..options.clone() };
+            let (fetched, _) = ready!(fetch.poll(&mut self.readable, cx, |fetched| {
+                if let Ok(reader) = MetadataReader::from_reader(fetched) {
+                    for _ in reader.with_options(lazy.clone()) {}
+                }
+            }))?;
+
+            Poll::Ready(metadata_reader(fetched, options, frame_ids))
+        })
+    }
+}
+
+fn metadata_reader<R>(readable: R,
+                      options: &ParseOptions,
+                      frame_ids: &Option<Vec<String>>)
+                      -> error::Result<MetadataReader<R>>
+    where R: Read + Seek
+{
+    let reader = MetadataReader::from_reader(readable)?.with_options(options.clone());
+
+    Ok(match *frame_ids {
+        Some(ref ids) => {
+            let ids = ids.iter().map(|id| id.as_str()).collect::<Vec<&str>>();
+            reader.with_frame_ids(&ids)
+        }
+        None => reader,
+    })
+}
+
+///
+/// Mp3 metadata writer over `AsyncRead + AsyncSeek + AsyncWrite`.
+///
+pub struct AsyncMetadataWriter<W> {
+    writable: W,
+    options: ParseOptions,
+}
+
+//
+// The steps of `AsyncMetadataWriter::write`.
+//
+enum WriteStep {
+    Probe,
+    Move(u64),
+    MoveRead(ReadAt, u64),
+    MoveWrite(WriteAt, u64),
+    Tag(WriteAt),
+    Frame1(WriteAt),
+    Flush,
+}
+
+impl<W> AsyncMetadataWriter<W>
+    where W: AsyncRead + AsyncSeek + AsyncWrite + Unpin
+{
+    pub fn new(writable: W) -> Self {
+        AsyncMetadataWriter {
+            writable: writable,
+            options: ParseOptions::default(),
+        }
+    }
+
+    ///
+    /// Same with `MetadataWriter::with_options`.
+    ///
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writable
+    }
+
+    ///
+    /// It write the units with the same way of `MetadataWriter::write`.
+    ///
+    /// Only the header and the last 128 bytes are read, and the audio is moved by chunks if
+    /// the size of the tag is changed.
+    /// The source can not be truncated by `AsyncWrite`. so it return the new length of the
+    /// source, and if it is shorter than before, the caller must truncate it.
+    /// e.g) `set_len` of the file.
+    ///
+    pub fn write<'a>(&'a mut self,
+                     units: Vec<Unit>,
+                     clean_write: bool)
+                     -> impl Future<Output = error::Result<u64>> + 'a {
+        let mut units = Some(units);
+        let options = self.options.clone();
+        let mut fetch = Fetch::new();
+        let mut rewrite = None;
+        let mut step = WriteStep::Probe;
+
+        poll_fn(move |cx| {
+            loop {
+                step = match step {
+                    WriteStep::Probe => {
+                        let (fetched, info) = ready!(fetch.poll(&mut self.writable,
+                                                                cx,
+                                                                |fetched| {
+                                                                    probe_reader_with(fetched,
+                                                                                      &options)
+                                                                }))?;
+
+                        let units = units.take().unwrap_or(Vec::new());
+                        let (has_frame1, all_bytes) = tag_bytes(units, clean_write, false)?;
+                        rewrite = Some(TagRewrite::new(all_bytes,
+                                                       has_frame1,
+                                                       clean_write,
+                                                       &info?,
+                                                       fetched.len())?);
+                        WriteStep::Move(0)
+                    }
+                    WriteStep::Move(moved) => {
+                        let rewrite = rewrite.as_ref().unwrap();
+                        match next_chunk(rewrite, moved) {
+                            Some((from, size)) => {
+                                WriteStep::MoveRead(ReadAt::new(from, size), moved)
+                            }
+                            None => {
+                                WriteStep::Tag(WriteAt::new(0, rewrite.tag_bytes.clone()))
+                            }
+                        }
+                    }
+                    WriteStep::MoveRead(ref mut read, moved) => {
+                        ready!(read.poll(&mut self.writable, cx))?;
+
+                        let bytes = ::std::mem::replace(&mut read.bytes, Vec::new());
+                        if bytes.len() < read.len {
+                            let err_msg = "The source ended while the audio is moved";
+                            return Poll::Ready(Err(Id3Error::new(error::ErrorKind::UnexpectedEof,
+                                                                 err_msg)));
+                        }
+
+                        let rewrite = rewrite.as_ref().unwrap();
+                        let to = read.offset + rewrite.tag_bytes.len() as u64 -
+                                 rewrite.audio_offset;
+                        WriteStep::MoveWrite(WriteAt::new(to, bytes), moved + read.len as u64)
+                    }
+                    WriteStep::MoveWrite(ref mut write, moved) => {
+                        ready!(write.poll(&mut self.writable, cx))?;
+                        WriteStep::Move(moved)
+                    }
+                    WriteStep::Tag(ref mut write) => {
+                        ready!(write.poll(&mut self.writable, cx))?;
+
+                        let rewrite = rewrite.as_ref().unwrap();
+                        match rewrite.frame1_bytes {
+                            Some(ref bytes) => {
+                                WriteStep::Frame1(WriteAt::new(rewrite.len() - 128, bytes.clone()))
+                            }
+                            None => WriteStep::Flush,
+                        }
+                    }
+                    WriteStep::Frame1(ref mut write) => {
+                        ready!(write.poll(&mut self.writable, cx))?;
+                        WriteStep::Flush
+                    }
+                    WriteStep::Flush => {
+                        ready!(Pin::new(&mut self.writable).poll_flush(cx))?;
+                        return Poll::Ready(Ok(rewrite.as_ref().unwrap().len()));
+                    }
+                };
+            }
+        })
+    }
+}
+
+//
+// The next chunk of the audio that is moved after 'moved' bytes are moved.
+// @return the offset and the size of the chunk.
+//
+// If the audio is moved forward, it is moved from the first. if not, from the last. so a
+// chunk is not overwritten before it is moved.
+//
+fn next_chunk(rewrite: &TagRewrite, moved: u64) -> Option<(u64, usize)> {
+    let tag_len = rewrite.tag_bytes.len() as u64;
+    let audio_len = rewrite.audio_end - rewrite.audio_offset;
+
+    if tag_len == rewrite.audio_offset || moved >= audio_len {
+        return None;
+    }
+
+    let size = cmp::min(BUF_SIZE as u64, audio_len - moved);
+    if tag_len < rewrite.audio_offset {
+        Some((rewrite.audio_offset + moved, size as usize))
+    } else {
+        Some((rewrite.audio_end - moved - size, size as usize))
+    }
+}
+
+//
+// It run 'pass' over the bytes that are fetched, and fetch the bytes that are read as 0 from
+// the source. it is repeated until 'pass' read only the fetched bytes.
+//
+struct Fetch {
+    fetched: Option<FetchedBytes>,
+    misses: Vec<(u64, u64)>,
+    read: Option<ReadAt>,
+}
+
+impl Fetch {
+    fn new() -> Self {
+        Fetch {
+            fetched: None,
+            misses: Vec::new(),
+            read: None,
+        }
+    }
+
+    //
+    // @return the fetched bytes and the result of the last 'pass'.
+    //
+    fn poll<R, F, T>(&mut self,
+                     readable: &mut R,
+                     cx: &mut Context,
+                     mut pass: F)
+                     -> Poll<error::Result<(FetchedBytes, T)>>
+        where R: AsyncRead + AsyncSeek + Unpin,
+              F: FnMut(&mut FetchedBytes) -> T
+    {
+        loop {
+            if self.fetched.is_none() {
+                let len = ready!(poll_seek(readable, cx, SeekFrom::End(0)))?;
+                self.fetched = Some(FetchedBytes::new(len));
+            }
+
+            if let Some(ref mut read) = self.read {
+                ready!(read.poll(readable, cx))?;
+            }
+
+            let fetched = self.fetched.as_mut().unwrap();
+
+            if let Some(read) = self.read.take() {
+                fetched.insert(read.offset, read.bytes);
+            }
+
+            if let Some((start, end)) = self.misses.pop() {
+                self.read = Some(ReadAt::new(start, (end - start) as usize));
+                continue;
+            }
+
+            let value = pass(fetched);
+            fetched.position = 0;
+
+            self.misses = fetched.take_misses();
+            if self.misses.is_empty() {
+                return Poll::Ready(Ok((self.fetched.take().unwrap(), value)));
+            }
+        }
+    }
+}
+
+//
+// A read of 'len' bytes at 'offset'. 'bytes' keep the bytes that are read, so it can be polled
+// again after `Poll::Pending`. it is shorter than 'len' if the source ended.
+//
+struct ReadAt {
+    offset: u64,
+    len: usize,
+    bytes: Vec<u8>,
+    filled: usize,
+    seeked: bool,
+}
+
+impl ReadAt {
+    fn new(offset: u64, len: usize) -> Self {
+        ReadAt {
+            offset: offset,
+            len: len,
+            bytes: vec![0u8; len],
+            filled: 0,
+            seeked: false,
+        }
+    }
+
+    fn poll<R>(&mut self, readable: &mut R, cx: &mut Context) -> Poll<Result<()>>
+        where R: AsyncRead + AsyncSeek + Unpin
+    {
+        if !self.seeked {
+            ready!(poll_seek(readable, cx, SeekFrom::Start(self.offset)))?;
+            self.seeked = true;
+        }
+
+        while self.filled < self.bytes.len() {
+            match ready!(Pin::new(&mut *readable).poll_read(cx, &mut self.bytes[self.filled..])) {
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Poll::Ready(Err(e)),
+                Ok(0) => {
+                    let filled = self.filled;
+                    self.bytes.truncate(filled);
+                }
+                Ok(n) => self.filled = self.filled + n,
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+//
+// A write of 'bytes' at 'offset'. it can be polled again after `Poll::Pending`.
+//
+struct WriteAt {
+    offset: u64,
+    bytes: Vec<u8>,
+    written: usize,
+    seeked: bool,
+}
+
+impl WriteAt {
+    fn new(offset: u64, bytes: Vec<u8>) -> Self {
+        WriteAt {
+            offset: offset,
+            bytes: bytes,
+            written: 0,
+            seeked: false,
+        }
+    }
+
+    fn poll<W>(&mut self, writable: &mut W, cx: &mut Context) -> Poll<Result<()>>
+        where W: AsyncWrite + AsyncSeek + Unpin
+    {
+        if !self.seeked {
+            ready!(poll_seek(writable, cx, SeekFrom::Start(self.offset)))?;
+            self.seeked = true;
+        }
+
+        while self.written < self.bytes.len() {
+            match ready!(Pin::new(&mut *writable).poll_write(cx, &self.bytes[self.written..])) {
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Poll::Ready(Err(e)),
+                Ok(0) => return Poll::Ready(Err(ErrorKind::WriteZero.into())),
+                Ok(n) => self.written = self.written + n,
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn poll_seek<R>(readable: &mut R, cx: &mut Context, pos: SeekFrom) -> Poll<Result<u64>>
+    where R: AsyncSeek + Unpin
+{
+    Pin::new(readable).poll_seek(cx, pos)
+}
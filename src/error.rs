@@ -0,0 +1,232 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::result;
+
+///
+/// The result of the reading and the writing of this crate.
+///
+pub type Result<T> = result::Result<T, Id3Error>;
+
+///
+/// The kind of `Id3Error`.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    // The header, the footer, the extended header or the frame header is invalid.
+    InvalidHeader,
+    // The major version of the tag is not 2, 3 or 4.
+    UnsupportedVersion,
+    // The size of a frame is larger than the remain bytes of the tag.
+    FrameTooLarge,
+    // A frame size of 2.4 is not synchsafe. it is read as a plain 32-bit integer.
+    NotSynchsafe,
+    // There is junk or a truncated frame after the last frame.
+    InvalidPadding,
+    // The bytes of a broken frame are skipped to find a next frame.
+    SkippedBytes,
+    // A compressed frame could not be decompressed.
+    Decompression,
+    // The decompressed size or the data length indicator is not same with the size of the body.
+    DataLength,
+    // A text could not be decoded or encoded.
+    Encoding,
+    // The units have different versions.
+    VersionMismatch,
+    // A size or a count is larger than `Limits`.
+    LimitExceeded,
+    // The source ended before the bytes that are needed.
+    UnexpectedEof,
+    // I/O error of the source.
+    Io,
+}
+
+///
+/// Error of this crate.
+///
+/// `offset` is the position in the source where it happend, and `frame_id` is the id of
+/// the frame that is read. both are `None` if it is not known.
+///
+/// The readers, the writers, Readable and Writable return it with `error::Result`.
+/// a `std::io::Error` of the source is converted to `ErrorKind::Io`, or to
+/// `ErrorKind::UnexpectedEof` if the source ended. if a `Id3Error` is wrapped in a
+/// `std::io::Error`, `Id3Error::from` unwrap it again.
+///
+#[derive(Debug)]
+pub struct Id3Error {
+    kind: ErrorKind,
+    message: String,
+    offset: Option<usize>,
+    frame_id: Option<String>,
+    cause: Option<io::Error>,
+}
+
+impl Id3Error {
+    pub fn new<S: Into<String>>(kind: ErrorKind, message: S) -> Self {
+        Id3Error {
+            kind: kind,
+            message: message.into(),
+            offset: None,
+            frame_id: None,
+            cause: None,
+        }
+    }
+
+    ///
+    /// It set the offset if it is not set yet.
+    ///
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        if self.offset.is_none() {
+            self.offset = Some(offset);
+        }
+        self
+    }
+
+    ///
+    /// It set the frame id if it is not set yet.
+    ///
+    pub fn with_frame_id<S: Into<String>>(mut self, frame_id: S) -> Self {
+        if self.frame_id.is_none() {
+            self.frame_id = Some(frame_id.into());
+        }
+        self
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn message(&self) -> &str {
+        self.message.as_str()
+    }
+
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+
+    pub fn frame_id(&self) -> Option<&str> {
+        self.frame_id.as_ref().map(|id| id.as_str())
+    }
+}
+
+impl fmt::Display for Id3Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}: {}", self.kind, self.message)?;
+
+        if let Some(ref frame_id) = self.frame_id {
+            write!(f, ", frame: '{}'", frame_id)?;
+        }
+
+        if let Some(offset) = self.offset {
+            write!(f, ", offset: {}", offset)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl error::Error for Id3Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.cause.as_ref().map(|e| e as &(dyn error::Error + 'static))
+    }
+}
+
+impl From<io::Error> for Id3Error {
+    fn from(err: io::Error) -> Self {
+        if err.get_ref().map_or(false, |e| e.is::<Id3Error>()) {
+            let inner = err.into_inner().unwrap();
+            return *inner.downcast::<Id3Error>().unwrap();
+        }
+
+        let kind = match err.kind() {
+            io::ErrorKind::UnexpectedEof => ErrorKind::UnexpectedEof,
+            _ => ErrorKind::Io,
+        };
+
+        let mut id3_error = Id3Error::new(kind, err.to_string());
+        id3_error.cause = Some(err);
+        id3_error
+    }
+}
+
+impl From<Id3Error> for io::Error {
+    fn from(err: Id3Error) -> Self {
+        let kind = match err.cause {
+            Some(ref cause) => cause.kind(),
+            None if err.kind == ErrorKind::UnexpectedEof => io::ErrorKind::UnexpectedEof,
+            None => io::ErrorKind::InvalidData,
+        };
+
+        io::Error::new(kind, err)
+    }
+}
+
+///
+/// The severity of `Diagnostic`.
+///
+/// - Warning: a problem that is recovered. e.g) a text that can not be decoded is read as "".
+/// - Error: a unit that can not be read.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+///
+/// A problem that is found while parsing.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub offset: Option<usize>,
+    pub frame_id: Option<String>,
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, err: &Id3Error) -> Self {
+        Diagnostic {
+            severity: severity,
+            offset: err.offset,
+            frame_id: err.frame_id.clone(),
+            kind: err.kind,
+            message: err.message.clone(),
+        }
+    }
+}
+
+///
+/// The spec violations that are found while reading a frame body.
+///
+/// If 'strict' is true, a violation is a error. if not, the frame is read leniently and the
+/// violation is kept in 'warnings'. So a frame is read only once in both modes.
+///
+#[derive(Debug, Default)]
+pub struct Violations {
+    pub strict: bool,
+    pub warnings: Vec<Id3Error>,
+}
+
+impl Violations {
+    pub fn new(strict: bool) -> Self {
+        Violations {
+            strict: strict,
+            warnings: Vec::new(),
+        }
+    }
+
+    ///
+    /// It return the violation as a error in strict mode. if not, it is kept as a warning.
+    ///
+    pub fn check(&mut self, err: Id3Error) -> Result<()> {
+        if self.strict {
+            return Err(err);
+        }
+
+        self.warnings.push(err);
+
+        Ok(())
+    }
+}
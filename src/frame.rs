@@ -4,11 +4,13 @@ extern crate regex;
 use self::encoding::{Encoding, DecoderTrap, EncoderTrap};
 use self::encoding::all::{ISO_8859_1, UTF_16LE, UTF_16BE, UTF_8};
 
+use error::{self, Id3Error, Result, Violations};
 use rw::{Readable, Writable};
 use frame::types::*;
 
+use std::cmp;
 use std::collections::HashMap;
-use std::io::{Cursor, Error, ErrorKind, Result, Write};
+use std::io::{Cursor, Write};
 use std::vec::Vec;
 
 //
@@ -64,7 +66,7 @@ macro_rules! convert_to_string {
 // It read a frame bytes by logical type.
 //
 macro_rules! frame_read {
-    (String, $value:expr, $readable:expr, $version:expr) => {
+    (String, $value:expr, $readable:expr, $version:expr, $violations:expr) => {
         {
             let _ = $version;
             let s = match $value {
@@ -78,7 +80,7 @@ macro_rules! frame_read {
         }
     };
 
-    (VersionString, $value:expr, $readable:expr, $version:expr) => {
+    (VersionString, $value:expr, $readable:expr, $version:expr, $violations:expr) => {
         {
             let id = match $version {
                 2 | 3 => $readable.read_string(3)?,
@@ -90,42 +92,42 @@ macro_rules! frame_read {
         }
     };
 
-    (Unsigned8, $value:expr, $readable:expr, $version:expr) => {
+    (Unsigned8, $value:expr, $readable:expr, $version:expr, $violations:expr) => {
         {
             let _ = $version;
             $readable.read_u8()?
         }
     };
 
-    (Unsigned16, $value:expr, $readable:expr, $version:expr) => {
+    (Unsigned16, $value:expr, $readable:expr, $version:expr, $violations:expr) => {
         {
             let _ = $version;
             $readable.read_u16()?
         }
     };
 
-    (Unsigned24, $value:expr, $readable:expr, $version:expr) => {
+    (Unsigned24, $value:expr, $readable:expr, $version:expr, $violations:expr) => {
         {
             let _ = $version;
             $readable.read_u24()?
         }
     };
     
-    (Unsigned32, $value:expr, $readable:expr, $version:expr) => {
+    (Unsigned32, $value:expr, $readable:expr, $version:expr, $violations:expr) => {
         {
             let _ = $version;
             $readable.read_u32()?
         }
     };
 
-    (Synchsafe, $value:expr, $readable:expr, $version:expr) => {
+    (Synchsafe, $value:expr, $readable:expr, $version:expr, $violations:expr) => {
         {
             let _ = $version;
             $readable.read_synchsafe()?
         }
     };
 
-    (NonUtf16String, $value:expr, $readable:expr, $version:expr) => {
+    (NonUtf16String, $value:expr, $readable:expr, $version:expr, $violations:expr) => {
         {
             let _ = $version;
             let r = $readable.read_non_utf16_string()?;
@@ -135,7 +137,7 @@ macro_rules! frame_read {
         }
     };
 
-    (Utf16String, $value:expr, $readable:expr, $version:expr) => {
+    (Utf16String, $value:expr, $readable:expr, $version:expr, $violations:expr) => {
         {
             let _ = $version;
             let r = $readable.read_utf16_string()?;
@@ -145,7 +147,7 @@ macro_rules! frame_read {
         }
     };
 
-    (Bytes, $value:expr, $readable:expr, $version:expr) => {
+    (Bytes, $value:expr, $readable:expr, $version:expr, $violations:expr) => {
         {
             let _ = $version;
             match $value {
@@ -159,49 +161,49 @@ macro_rules! frame_read {
         }
     };
 
-    (TextEncoding, $value:expr, $readable:expr, $version:expr) => {
+    (TextEncoding, $value:expr, $readable:expr, $version:expr, $violations:expr) => {
         {
             let _ = $version;
-            types::to_encoding($readable.read_u8()?)
+            read_text_encoding($readable, $violations)?
         }
     };
 
-    (PictureType, $value:expr, $readable:expr, $version:expr) => {
+    (PictureType, $value:expr, $readable:expr, $version:expr, $violations:expr) => {
         {
             let _ = $version;
             types::to_picture_type($readable.read_u8()?)
         }
     };
 
-    (ReceivedAs, $value:expr, $readable:expr, $version:expr) => {
+    (ReceivedAs, $value:expr, $readable:expr, $version:expr, $violations:expr) => {
         {
             let _ = $version;
             types::to_received_as($readable.read_u8()?)
         }
     };
 
-    (InterpolationMethod, $value:expr, $readable:expr, $version:expr) => {
+    (InterpolationMethod, $value:expr, $readable:expr, $version:expr, $violations:expr) => {
         {
             let _ = $version;
             types::to_interpolation_method($readable.read_u8()?)
         }
     };
 
-    (TimestampFormat, $value:expr, $readable:expr, $version:expr) => {
+    (TimestampFormat, $value:expr, $readable:expr, $version:expr, $violations:expr) => {
         {
             let _ = $version;
             types::to_timestamp_format($readable.read_u8()?)
         }
     };
 
-    (ContentType, $value:expr, $readable:expr, $version:expr) => {
+    (ContentType, $value:expr, $readable:expr, $version:expr, $violations:expr) => {
         {
             let _ = $version;
             types::to_content_type($readable.read_u8()?)
         }
     };
 
-    (EncodedString, $value:expr, $readable:expr, $version:expr) => {
+    (EncodedString, $value:expr, $readable:expr, $version:expr, $violations:expr) => {
         {
             let _ = $version;
 
@@ -214,12 +216,14 @@ macro_rules! frame_read {
             let encoding = $readable.read_u8()?;
             let _ = $readable.position(curr_pos)?;
 
-            fn decode(data: &Vec<u8>, decode: ::std::result::Result<String, ::std::borrow::Cow<'static, str>>) -> String {
+            fn decode(data: &Vec<u8>, decode: ::std::result::Result<String, ::std::borrow::Cow<'static, str>>, violations: &mut Violations) -> Result<String> {
                 match decode {
-                    Ok(text) => text,
+                    Ok(text) => Ok(text),
                     Err(e) => {
                         warn!("Encoding error {:?}, {:?}", e, data);
-                        "".to_string()
+                        let err = Id3Error::new(error::ErrorKind::Encoding, e.into_owned());
+                        violations.check(err)?;
+                        Ok("".to_string())
                     }
                 }
             }
@@ -232,13 +236,14 @@ macro_rules! frame_read {
                         "".to_string()
                     } else {
                         data.push(0);
-                        decode(&data, UTF_16LE.decode(&data[2..], DecoderTrap::Strict))
+                        let text = UTF_16LE.decode(&data[2..], DecoderTrap::Strict);
+                        decode(&data, text, $violations)?
                     }
 
                 }
                 2 => {
                     let data = $readable.read_utf16_bytes()?;
-                    decode(&data, UTF_16BE.decode(&data, DecoderTrap::Strict))
+                    decode(&data, UTF_16BE.decode(&data, DecoderTrap::Strict), $violations)?
                 }
                 _ => $readable.read_non_utf16_string()?
             }
@@ -414,12 +419,21 @@ macro_rules! id3 {
 
         impl $name {
             pub fn read(readable: &mut Readable, version: u8, id: &str) -> Result<$name> {
+                $name::read_with(readable, version, id, &mut Violations::default())
+            }
+
+            pub fn read_with(readable: &mut dyn Readable,
+                             version: u8,
+                             id: &str,
+                             violations: &mut Violations)
+                             -> Result<$name> {
 
                 let _ = id;
+                let _ = &violations;
 
                 let ret = $name {
                     $(
-                        $attr_name : frame_read!($attr_type, $value, readable, version)
+                        $attr_name : frame_read!($attr_type, $value, readable, version, violations)
                     ),*
                 };
 
@@ -504,6 +518,221 @@ id3!(Head {
     size: Synchsafe = -1,
 });
 
+///
+/// # ID3V2 Extended Header
+///
+/// - [V2.3](http://id3.org/id3v2.3.0#ID3v2_extended_header)
+/// - [V2.4](http://id3.org/id3v2.4.0-structure) > 3.2. Extended header
+///
+/// `size` is the size that is read. it is recalculated when it is written.
+/// - 2.3: the size excluding itself. 6 or 10.
+/// - 2.4: the whole size of extended header.
+///
+/// `flags` is 2 bytes of flags in 2.3 and the flag bytes following 'number of flag bytes' in 2.4.
+///
+/// Unknown bytes following the known fields are kept in `extra`.
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExtendedHeader {
+    pub size: u32,
+    pub flags: Vec<u8>,
+    // 2.3 only
+    pub padding_size: Option<u32>,
+    pub crc32: Option<u32>,
+    // 2.4 only
+    pub tag_is_update: bool,
+    // 2.4 only
+    pub restrictions: Option<u8>,
+    pub extra: Vec<u8>,
+}
+
+impl ExtendedHeader {
+    pub fn read(readable: &mut dyn Readable, version: u8) -> Result<Self> {
+        match version {
+            3 => Self::read3(readable),
+            4 => Self::read4(readable),
+            _ => {
+                Err(Id3Error::new(error::ErrorKind::UnsupportedVersion,
+                                  format!("No extended header in version {}", version)))
+            }
+        }
+    }
+
+    fn read3(readable: &mut dyn Readable) -> Result<Self> {
+        let size = readable.read_u32()?;
+
+        if size != 6 && size != 10 {
+            return Err(Id3Error::new(error::ErrorKind::InvalidHeader,
+                                     format!("Invalid extended header size: {}", size)));
+        }
+
+        let flags = readable.read_bytes(2)?;
+        let padding_size = readable.read_u32()?;
+
+        //
+        // If the flag of CRC data is set, four byte of CRC-32 follow the padding size.
+        //
+        let mut remain = size - 6;
+        let crc32 = if flags[0] & types::BIT7 != 0 && remain >= 4 {
+            remain = remain - 4;
+            Some(readable.read_u32()?)
+        } else {
+            None
+        };
+
+        Ok(ExtendedHeader {
+            size: size,
+            flags: flags,
+            padding_size: Some(padding_size),
+            crc32: crc32,
+            tag_is_update: false,
+            restrictions: None,
+            extra: readable.read_bytes(remain as usize)?,
+        })
+    }
+
+    fn read4(readable: &mut dyn Readable) -> Result<Self> {
+        let size = readable.read_synchsafe()?;
+        let flag_bytes = readable.read_u8()?;
+
+        if size < 6 || flag_bytes != 1 {
+            return Err(Id3Error::new(error::ErrorKind::InvalidHeader,
+                                     format!("Invalid extended header. size: {}, number of \
+                                              flag bytes: {}",
+                                             size,
+                                             flag_bytes)));
+        }
+
+        let flags = readable.read_bytes(flag_bytes as usize)?;
+        let mut data = Cursor::new(readable.read_bytes(size as usize - 5 - flag_bytes as usize)?);
+
+        //
+        // Every flag that is set has a length byte and data in the order of flag bits.
+        //
+        fn flag_data(data: &mut Cursor<Vec<u8>>, expected: u8) -> Result<Vec<u8>> {
+            let len = data.read_u8()?;
+            if len != expected {
+                return Err(Id3Error::new(error::ErrorKind::InvalidHeader,
+                                         format!("Invalid extended header flag data length: \
+                                                  {}",
+                                                 len)));
+            }
+            data.read_bytes(len as usize)
+        }
+
+        let tag_is_update = flags[0] & types::BIT6 != 0;
+        if tag_is_update {
+            flag_data(&mut data, 0)?;
+        }
+
+        let crc32 = if flags[0] & types::BIT5 != 0 {
+            //
+            // 35 bit of synchsafe integer.
+            //
+            let bytes = flag_data(&mut data, 5)?;
+            Some(bytes.iter().fold(0u64, |v, b| v << 7 | (b & 0x7f) as u64) as u32)
+        } else {
+            None
+        };
+
+        let restrictions = if flags[0] & types::BIT4 != 0 {
+            Some(flag_data(&mut data, 1)?[0])
+        } else {
+            None
+        };
+
+        Ok(ExtendedHeader {
+            size: size,
+            flags: flags,
+            padding_size: None,
+            crc32: crc32,
+            tag_is_update: tag_is_update,
+            restrictions: restrictions,
+            extra: data.all_bytes()?,
+        })
+    }
+
+    ///
+    /// The size of extended header, in byte, when it is written.
+    ///
+    pub fn length(&self, version: u8) -> u32 {
+        match version {
+            3 => 4 + 6 + if self.crc32.is_some() { 4 } else { 0 } + self.extra.len() as u32,
+            _ => {
+                let mut len = 4 + 1 + cmp::max(self.flags.len(), 1) as u32;
+                if self.tag_is_update {
+                    len = len + 1;
+                }
+                if self.crc32.is_some() {
+                    len = len + 1 + 5;
+                }
+                if self.restrictions.is_some() {
+                    len = len + 1 + 1;
+                }
+                len + self.extra.len() as u32
+            }
+        }
+    }
+
+    pub fn write(&self, writable: &mut dyn Writable, version: u8) -> Result<()> {
+        let length = self.length(version);
+
+        match version {
+            3 => {
+                let mut flags = self.flags.clone();
+                flags.resize(2, 0);
+                flags[0] = match self.crc32 {
+                    Some(_) => flags[0] | types::BIT7,
+                    None => flags[0] & !types::BIT7,
+                };
+
+                writable.write_u32(length - 4)?;
+                writable.write(&flags)?;
+                writable.write_u32(self.padding_size.unwrap_or(0))?;
+                if let Some(crc32) = self.crc32 {
+                    writable.write_u32(crc32)?;
+                }
+            }
+            _ => {
+                let mut flags = self.flags.clone();
+                if flags.len() == 0 {
+                    flags.push(0);
+                }
+                flags[0] = flags[0] & !(types::BIT6 | types::BIT5 | types::BIT4);
+                if self.tag_is_update {
+                    flags[0] = flags[0] | types::BIT6;
+                }
+                if self.crc32.is_some() {
+                    flags[0] = flags[0] | types::BIT5;
+                }
+                if self.restrictions.is_some() {
+                    flags[0] = flags[0] | types::BIT4;
+                }
+
+                writable.write_synchsafe(length)?;
+                writable.write_u8(flags.len() as u8)?;
+                writable.write(&flags)?;
+                if self.tag_is_update {
+                    writable.write_u8(0)?;
+                }
+                if let Some(crc32) = self.crc32 {
+                    writable.write_u8(5)?;
+                    writable.write_u8(((crc32 >> 28) & 0x7f) as u8)?;
+                    writable.write_synchsafe(crc32)?;
+                }
+                if let Some(restrictions) = self.restrictions {
+                    writable.write_u8(1)?;
+                    writable.write_u8(restrictions)?;
+                }
+            }
+        }
+
+        writable.write(&self.extra)?;
+
+        Ok(())
+    }
+}
+
 ///
 /// # Frame Header V2.2
 ///
@@ -519,12 +748,25 @@ id3!(FrameHeaderV2 {
 ///
 /// [See](http://id3.org/id3v2.3.0#ID3v2_frame_overview)
 ///
+/// The bytes that follow the header by the flags are kept in the header. they are set by
+/// `MetadataReader`, and written by `write`.
+///
+/// - data_length: the decompressed size if the frame is compressed.
+/// - encryption_method: the method symbol if the frame is encrypted. see ENCR.
+/// - group_symbol: the group symbol if the frame is grouped. see GRID.
+///
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct FrameHeaderV3 {
     pub id: String,
     pub size: u32,
     pub status_flag: u8,
     pub encoding_flag: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_length: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_symbol: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption_method: Option<u8>,
 }
 
 impl FrameHeaderV3 {
@@ -542,6 +784,9 @@ impl FrameHeaderV3 {
             size: size,
             status_flag: status_flag,
             encoding_flag: encoding_flag,
+            data_length: None,
+            group_symbol: None,
+            encryption_method: None,
         })
     }
 
@@ -563,18 +808,37 @@ impl FrameHeaderV3 {
         writable.write_u8(self.status_flag)?;
         writable.write_u8(self.encoding_flag)?;
 
-        if self.has_flag(FrameHeaderFlag::GroupIdentity) {
-            writable.write_u8(0)?;
+        if self.has_flag(FrameHeaderFlag::Compression) {
+            writable.write_u32(self.data_length.unwrap_or(self.size))?;
         }
         if self.has_flag(FrameHeaderFlag::Encryption) {
-            writable.write_u8(0)?;
+            writable.write_u8(self.encryption_method.unwrap_or(0))?;
         }
-        if self.has_flag(FrameHeaderFlag::Compression) {
-            writable.write_u32(0)?;
+        if self.has_flag(FrameHeaderFlag::GroupIdentity) {
+            writable.write_u8(self.group_symbol.unwrap_or(0))?;
         }
 
         Ok(())
     }
+
+    ///
+    /// It read the bytes that follow the header by the flags. 'bytes' is the frame body.
+    /// In 2.3, they are in the order of the decompressed size, the encryption method and the
+    /// group symbol.
+    ///
+    pub fn read_flag_bytes(&mut self, bytes: &[u8]) {
+        let mut readable = Cursor::new(bytes);
+
+        if self.has_flag(FrameHeaderFlag::Compression) {
+            self.data_length = readable.read_u32().ok();
+        }
+        if self.has_flag(FrameHeaderFlag::Encryption) {
+            self.encryption_method = readable.read_u8().ok();
+        }
+        if self.has_flag(FrameHeaderFlag::GroupIdentity) {
+            self.group_symbol = readable.read_u8().ok();
+        }
+    }
 }
 
 
@@ -584,12 +848,26 @@ impl FrameHeaderV3 {
 ///
 /// [See](http://id3.org/id3v2.4.0-structure) > 4. ID3v2 frames overview
 ///
+/// The bytes that follow the header by the flags are kept in the header. they are set by
+/// `MetadataReader`, and written by `write`.
+///
+/// - group_symbol: the group symbol if the frame is grouped. see GRID.
+/// - encryption_method: the method symbol if the frame is encrypted. see ENCR.
+/// - data_length: the data length indicator. it is the size of the body before it is
+///   unsynchronised and compressed.
+///
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct FrameHeaderV4 {
     pub id: String,
     pub size: u32,
     pub status_flag: u8,
     pub encoding_flag: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_length: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_symbol: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption_method: Option<u8>,
 }
 
 impl FrameHeaderV4 {
@@ -607,6 +885,9 @@ impl FrameHeaderV4 {
             size: size,
             status_flag: status_flag,
             encoding_flag: encoding_flag,
+            data_length: None,
+            group_symbol: None,
+            encryption_method: None,
         })
     }
 
@@ -630,17 +911,36 @@ impl FrameHeaderV4 {
         writable.write_u8(self.encoding_flag)?;
 
         if self.has_flag(FrameHeaderFlag::GroupIdentity) {
-            writable.write_u8(0)?;
+            writable.write_u8(self.group_symbol.unwrap_or(0))?;
         }
         if self.has_flag(FrameHeaderFlag::Encryption) {
-            writable.write_u8(0)?;
+            writable.write_u8(self.encryption_method.unwrap_or(0))?;
         }
         if self.has_flag(FrameHeaderFlag::DataLength) {
-            writable.write_u32(0)?;
+            writable.write_synchsafe(self.data_length.unwrap_or(self.size))?;
         }
 
         Ok(())
     }
+
+    ///
+    /// It read the bytes that follow the header by the flags. 'bytes' is the frame body.
+    /// In 2.4, they are in the order of the group symbol, the encryption method and the data
+    /// length indicator.
+    ///
+    pub fn read_flag_bytes(&mut self, bytes: &[u8]) {
+        let mut readable = Cursor::new(bytes);
+
+        if self.has_flag(FrameHeaderFlag::GroupIdentity) {
+            self.group_symbol = readable.read_u8().ok();
+        }
+        if self.has_flag(FrameHeaderFlag::Encryption) {
+            self.encryption_method = readable.read_u8().ok();
+        }
+        if self.has_flag(FrameHeaderFlag::DataLength) {
+            self.data_length = readable.read_synchsafe().ok();
+        }
+    }
 }
 
 ///
@@ -923,6 +1223,65 @@ impl FrameHeaderDefault for FrameHeader {
     }
 }
 
+impl FrameHeader {
+    ///
+    /// The frame id. it is borrowed, unlike `FrameHeaderDefault::id`.
+    ///
+    pub fn id_str(&self) -> &str {
+        match self {
+            &FrameHeader::V22(ref header) => &header.id,
+            &FrameHeader::V23(ref header) => &header.id,
+            &FrameHeader::V24(ref header) => &header.id,
+        }
+    }
+
+    ///
+    /// The decompressed size of 2.3, or the data length indicator of 2.4.
+    ///
+    pub fn data_length(&self) -> Option<u32> {
+        match self {
+            &FrameHeader::V22(_) => None,
+            &FrameHeader::V23(ref header) => header.data_length,
+            &FrameHeader::V24(ref header) => header.data_length,
+        }
+    }
+
+    pub fn set_data_length(&mut self, data_length: Option<u32>) {
+        match self {
+            &mut FrameHeader::V22(_) => (),
+            &mut FrameHeader::V23(ref mut header) => header.data_length = data_length,
+            &mut FrameHeader::V24(ref mut header) => header.data_length = data_length,
+        }
+    }
+
+    pub fn group_symbol(&self) -> Option<u8> {
+        match self {
+            &FrameHeader::V22(_) => None,
+            &FrameHeader::V23(ref header) => header.group_symbol,
+            &FrameHeader::V24(ref header) => header.group_symbol,
+        }
+    }
+
+    pub fn encryption_method(&self) -> Option<u8> {
+        match self {
+            &FrameHeader::V22(_) => None,
+            &FrameHeader::V23(ref header) => header.encryption_method,
+            &FrameHeader::V24(ref header) => header.encryption_method,
+        }
+    }
+
+    ///
+    /// It read the bytes that follow the header by the flags. 'bytes' is the frame body.
+    ///
+    pub fn read_flag_bytes(&mut self, bytes: &[u8]) {
+        match self {
+            &mut FrameHeader::V22(_) => (),
+            &mut FrameHeader::V23(ref mut header) => header.read_flag_bytes(bytes),
+            &mut FrameHeader::V24(ref mut header) => header.read_flag_bytes(bytes),
+        }
+    }
+}
+
 impl FlagAware<FrameHeaderFlag> for FrameHeader {
     fn has_flag(&self, flag: FrameHeaderFlag) -> bool {
         match self {
@@ -1211,9 +1570,9 @@ id3!(RVRB {
 ///
 /// Seek frame
 ///
-///**Not yet tested!**
+/// 'offset_to_next_tag' is calculated from the end of this tag to the beginning of the next tag.
 ///
-id3!(SEEK { next_tag: String = 0 });
+id3!(SEEK { offset_to_next_tag: Unsigned32 = -1 });
 
 ///
 /// Signature frame
@@ -1317,7 +1676,16 @@ pub struct ETCO {
 
 impl ETCO {
     pub fn read(readable: &mut Readable, version: u8, id: &str) -> Result<ETCO> {
+        ETCO::read_with(readable, version, id, &mut Violations::default())
+    }
+
+    pub fn read_with(readable: &mut dyn Readable,
+                     version: u8,
+                     id: &str,
+                     violations: &mut Violations)
+                     -> Result<ETCO> {
         let _ = version;
+        let _ = violations;
         let _ = id;
         let timestamp_format = types::to_timestamp_format(readable.read_u8()?);
         let mut event_timing_codes: Vec<EventTimingCode> = Vec::new();
@@ -1377,6 +1745,22 @@ impl Look for ETCO {
     }
 }
 
+//
+// The text encoding must be 0 ~ 3. if it is not and not strict, it is ISO-8859-1.
+//
+fn read_text_encoding(readable: &mut dyn Readable,
+                      violations: &mut Violations)
+                      -> Result<TextEncoding> {
+    let encoding = readable.read_u8()?;
+
+    if encoding > 3 {
+        violations.check(Id3Error::new(error::ErrorKind::Encoding,
+                                       format!("Invalid text encoding: {}", encoding)))?;
+    }
+
+    Ok(types::to_encoding(encoding))
+}
+
 ///
 /// For all the T??? types
 ///
@@ -1388,17 +1772,34 @@ pub struct TEXT {
 
 impl TEXT {
     pub fn read(readable: &mut Readable, version: u8, id: &str) -> Result<TEXT> {
+        TEXT::read_with(readable, version, id, &mut Violations::default())
+    }
+
+    ///
+    /// In strict mode, a text that can not be decoded is a error.
+    /// if not, it is replaced with "" or "0" for TBPM.
+    ///
+    pub fn read_with(readable: &mut dyn Readable,
+                     version: u8,
+                     id: &str,
+                     violations: &mut Violations)
+                     -> Result<TEXT> {
         fn _default(id: &str,
-                    decode: ::std::result::Result<String, ::std::borrow::Cow<'static, str>>)
-                    -> String {
+                    decode: ::std::result::Result<String, ::std::borrow::Cow<'static, str>>,
+                    violations: &mut Violations)
+                    -> Result<String> {
             match decode {
-                Ok(text) => text,
+                Ok(text) => Ok(text),
                 Err(e) => {
                     debug!("TEXT Error {}, {:?}", id, e);
+                    let err = Id3Error::new(error::ErrorKind::Encoding, e.into_owned())
+                        .with_frame_id(id);
+                    violations.check(err)?;
+
                     if id == id::TBPM || id == id::TBP {
-                        "0".to_string()
+                        Ok("0".to_string())
                     } else {
-                        "".to_string()
+                        Ok("".to_string())
                     }
                 }
             }
@@ -1414,13 +1815,21 @@ impl TEXT {
         }
 
         let _ = version;
-        let text_encoding = types::to_encoding(readable.read_u8()?);
+        let text_encoding = read_text_encoding(readable, violations)?;
         let data = readable.all_bytes()?;
         let text = match text_encoding {
-            TextEncoding::ISO88591 => _default(id, ISO_8859_1.decode(&data, DecoderTrap::Strict)),
-            TextEncoding::UTF16LE => _default(id, UTF_16LE.decode(&data, DecoderTrap::Strict)),
-            TextEncoding::UTF16BE => _default(id, UTF_16BE.decode(&data, DecoderTrap::Strict)),
-            TextEncoding::UTF8 => _default(id, UTF_8.decode(&data, DecoderTrap::Strict)),
+            TextEncoding::ISO88591 => {
+                _default(id, ISO_8859_1.decode(&data, DecoderTrap::Strict), violations)?
+            }
+            TextEncoding::UTF16LE => {
+                _default(id, UTF_16LE.decode(&data, DecoderTrap::Strict), violations)?
+            }
+            TextEncoding::UTF16BE => {
+                _default(id, UTF_16BE.decode(&data, DecoderTrap::Strict), violations)?
+            }
+            TextEncoding::UTF8 => {
+                _default(id, UTF_8.decode(&data, DecoderTrap::Strict), violations)?
+            }
         };
 
         Ok(TEXT {
@@ -1440,7 +1849,9 @@ impl TEXT {
             TextEncoding::UTF8 => UTF_8.encode(self.text.as_str(), EncoderTrap::Strict),
         } {
             Ok(text) => text,
-            Err(msg) => return Err(Error::new(ErrorKind::InvalidInput, msg.to_owned().to_string())),
+            Err(msg) => {
+                return Err(Id3Error::new(error::ErrorKind::Encoding, msg.to_owned().to_string()))
+            }
         };
 
         match self.text_encoding {
@@ -1972,19 +2383,32 @@ macro_rules! define_id_to_framebody {
         $( $id:ident = $body:ident : $frame:ident ),*
     ) => (
 
-        pub fn read_framebody_with_id(id: &str, version: u8, mut readable: Cursor<Vec<u8>>) 
+        pub fn read_framebody_with_id(id: &str, version: u8, readable: Cursor<Vec<u8>>) 
             -> Result<FrameBody> {
+                read_framebody(id, version, readable, &mut Violations::default())
+        }
 
-                trace!("id:{}, version:{}", id, version);
+        ///
+        /// A spec violation and an unknown frame id are checked by 'violations'.
+        /// if it is not strict, an unknown frame is read as `FrameBody::Unknown`.
+        ///
+        pub fn read_framebody(id: &str, version: u8, mut readable: Cursor<Vec<u8>>, violations: &mut Violations) 
+            -> Result<FrameBody> {
+
+                trace!("id:{}, version:{}, strict: {}", id, version, violations.strict);
 
                 let frame_body = match id.as_ref() {
 
-                    $( stringify!($id) => FrameBody::$body($frame::read(&mut readable, version, id)?) ),*
+                    $( stringify!($id) => FrameBody::$body($frame::read_with(&mut readable, version, id, violations)?) ),*
                     
                     , 
                     _ => {
                         warn!("No frame id found!! '{}'", id);
-                        FrameBody::TEXT(TEXT::read(&mut readable, version, id)?)
+                        let err = Id3Error::new(error::ErrorKind::InvalidHeader, "Unknown frame id")
+                            .with_frame_id(id);
+                        violations.check(err)?;
+
+                        FrameBody::Unknown { id: id.to_string(), raw: readable.all_bytes()? }
                     }
                 };
 
@@ -2025,7 +2449,7 @@ macro_rules! define_id_to_framebody {
                 let meta_id = stringify!($id);
 
                 if meta_id.len() == 3 {
-                    m.insert(id, stringify!($frame));
+                    m.insert(meta_id, stringify!($body));
                 }
 
             );*
@@ -2060,6 +2484,9 @@ macro_rules! define_framebody {
             /// It is used for unknown frame when to parse
             SKIP(String, Vec<u8>),
 
+            /// A frame of unknown id. the body is kept as it is and written back unchanged.
+            Unknown { id: String, raw: Vec<u8> },
+
             /// Invalid frame
             INVALID(String)
         }
@@ -2088,6 +2515,10 @@ macro_rules! define_framebody {
 
         pub fn framebody_as_bytes(frame_body: &FrameBody, version: u8) -> Result<(&str, Vec<u8>)> {
 
+            if let &FrameBody::Unknown { ref id, ref raw } = frame_body {
+                return Ok((id.as_str(), raw.clone()));
+            }
+
             let mut writable = Cursor::new(vec![0u8; 0]);
 
             match frame_body {
@@ -155,6 +155,10 @@ extern crate log;
 extern crate serde_derive;
 extern crate serde_json;
 
+pub mod error;
 pub mod rw;
 pub mod frame;
-pub mod metadata;
\ No newline at end of file
+pub mod metadata;
+pub mod mmap;
+#[cfg(feature = "async")]
+pub mod async_metadata;
\ No newline at end of file
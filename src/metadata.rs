@@ -5,40 +5,152 @@ use self::flate2::Compression;
 use self::flate2::read::ZlibDecoder;
 use self::flate2::write::ZlibEncoder;
 
+use error::{self, Diagnostic, Id3Error, Result, Severity, Violations};
 use frame::*;
 use frame::types::*;
 use rw::{Readable, Writable};
 
-use std::cell::RefCell;
+use std::borrow::Cow;
 use std::fs::{File, OpenOptions};
-use std::io::{Cursor, Error, ErrorKind, Result, Read, Write};
+use std::io::{Cursor, Read, Seek, Write};
 use std::iter::Iterator;
-use std::rc::Rc;
+use std::result;
 use std::vec::Vec;
 
-type FrameReadable = Rc<RefCell<Box<Cursor<Vec<u8>>>>>;
-
 ///
 /// Parsing results.
 ///
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Unit {
     Header(Head),
-    // TODO not yet implemented
-    ExtendedHeader(Vec<u8>),
+    ExtendedHeader(ExtendedHeader),
     FrameV2(FrameHeader, FrameBody),
+    // A frame that is read with `ParseOptions::lazy`.
+    LazyFrameV2(LazyFrame),
+    // The bytes after the last frame. 'offset' is the position in the source.
+    // if 'all_zero' is false, there is junk or a truncated frame, and 'bytes' keep them.
+    // 'bytes' is empty if they are all zero.
+    Padding {
+        offset: usize,
+        size: usize,
+        all_zero: bool,
+        bytes: Vec<u8>,
+    },
+    // 2.4 only. it is a copy of the header but the 'tag_id' is "3DI".
+    Footer(Head),
     FrameV1(Frame1),
 }
 
+///
+/// A frame that the body is not decoded yet.
+///
+/// 'bytes' is the frame body as it is in the tag. e.g) it is compressed if the compression flag
+/// is set, and the extra bytes of the flags are included.
+/// 'limits' are the limits that the frame is read with. `decode` decodes with them.
+/// 'plain_size' is true if the size of a 2.4 frame is read as a plain 32-bit integer, not
+/// synchsafe. `MetadataWriter::lazy_frame` write the size with the same way.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LazyFrame {
+    pub header: FrameHeader,
+    pub bytes: Vec<u8>,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub limits: Limits,
+    #[serde(default)]
+    pub plain_size: bool,
+}
+
+impl LazyFrame {
+    ///
+    /// It read a frame header and the bytes of the body.
+    ///
+    pub fn read(readable: &mut Cursor<Vec<u8>>, version: u8) -> Result<Self> {
+        Self::read_with(readable, version, &ParseOptions::default())
+    }
+
+    ///
+    /// It read a frame with the options. the size of the frame must not be larger than
+    /// `Limits::max_frame_size`.
+    ///
+    pub fn read_with(readable: &mut Cursor<Vec<u8>>,
+                     version: u8,
+                     options: &ParseOptions)
+                     -> Result<Self> {
+        let position = Cursor::position(readable) as usize;
+        let plain_size = version == 4 && plain_frame_size(readable.get_ref(), position).is_some();
+
+        let mut header = read_frame_header(readable, version)?;
+        check_limit("Frame size", header.size() as usize, options.limits.max_frame_size)
+            .map_err(|e| e.with_frame_id(header.id()))?;
+
+        let bytes = readable.read_bytes(header.size() as usize)?;
+        header.read_flag_bytes(&bytes);
+
+        Ok(LazyFrame {
+            header: header,
+            bytes: bytes,
+            limits: options.limits.clone(),
+            plain_size: plain_size,
+        })
+    }
+
+    pub fn version(&self) -> u8 {
+        match self.header {
+            FrameHeader::V22(_) => 2,
+            FrameHeader::V23(_) => 3,
+            FrameHeader::V24(_) => 4,
+        }
+    }
+
+    ///
+    /// It decode the frame body with the limits that the frame is read with.
+    ///
+    pub fn decode(&self) -> Result<FrameBody> {
+        self.decode_with(&ParseOptions { limits: self.limits.clone(), ..ParseOptions::default() })
+    }
+
+    ///
+    /// It decode the frame body with the options.
+    ///
+    pub fn decode_with(&self, options: &ParseOptions) -> Result<FrameBody> {
+        self.decode_with_violations(options, &mut Violations::new(options.strict))
+    }
+
+    ///
+    /// It decode the frame body, and the spec violations are checked by 'violations'.
+    /// if it is not strict, they are kept in `Violations::warnings`.
+    ///
+    pub fn decode_with_violations(&self,
+                                  options: &ParseOptions,
+                                  violations: &mut Violations)
+                                  -> Result<FrameBody> {
+        let id = self.header.id();
+        let body_bytes = frame_content(&self.header, &self.bytes, options)?.into_owned();
+
+        //
+        // If frame is encrypted, this frame can not read.
+        //
+        if self.header.has_flag(FrameHeaderFlag::Encryption) {
+            debug!("'{}' is encrypted", id);
+            return Ok(FrameBody::SKIP(id, body_bytes));
+        }
+
+        read_framebody(&id, self.version(), Cursor::new(body_bytes), violations)
+    }
+}
+
 //
 // Internal parsing state.
 //
 #[derive(Debug, Clone)]
 enum Status {
     None,
+    Head(Head),
     ExtendedHeader(Head),
-    Frame(Head, FrameReadable),
-    Frame1,
+    Frame(Head),
+    Padding(Head),
+    Footer(Head),
+    NextTag,
     Error,
 }
 
@@ -50,19 +162,86 @@ pub trait ReadOp: Readable {
     /// it read header that is 10 byte length.
     ///
     fn head(&mut self) -> Result<Unit> {
-        Ok(Unit::Header(Head::read(&mut self.to_readable(10)?, 0, "")?))
+        let head = Head::read(&mut self.to_readable(10)?, 0, "")?;
+
+        if head.tag_id != "ID3" {
+            let err_msg = "Invalid tag id";
+            debug!("{}: {:?}", err_msg, head.tag_id);
+            return Err(Id3Error::new(error::ErrorKind::InvalidHeader, err_msg));
+        }
+
+        if head.version < 2 || head.version > 4 {
+            let err_msg = format!("Unsupported version: 2.{}", head.version);
+            warn!("{}", err_msg);
+            return Err(Id3Error::new(error::ErrorKind::UnsupportedVersion, err_msg));
+        }
+
+        Ok(Unit::Header(head))
     }
 
     ///
-    /// If tag version is 4, the size of extened header is calcurated as synchsize.
+    /// It find a tag that appended at the end of a file.
+    /// the tag is located by the footer that is just before the frame1 or the end of a file.
+    /// @return the position of the header of the appended tag.
     ///
-    fn ext_head(&mut self, head: &Head) -> Result<Unit> {
-        let size = match head.version {
-            3 => self.read_u32()?,
-            _ => self.read_synchsafe()?,
-        };
+    fn appended_tag(&mut self) -> Result<Option<usize>> {
+        let file_len = self.position_end(0)?;
+
+        let mut ends = vec![file_len];
+        if file_len >= 128 {
+            self.position_end(-128)?;
+            if self.look_string(3)? == "TAG" {
+                ends.push(file_len - 128);
+            }
+        }
+
+        for end in ends {
+            if end < 20 {
+                continue;
+            }
+
+            self.position(end - 10)?;
+            if self.look_string(3)? != "3DI" {
+                continue;
+            }
+
+            let footer = Head::read(&mut self.to_readable(10)?, 4, "")?;
+            let tag_len = footer.size as usize + 20;
+            if tag_len <= end {
+                return Ok(Some(end - tag_len));
+            }
+        }
+
+        Ok(None)
+    }
+
+    ///
+    /// It find a header from 'start' to 'start + window'.
+    /// @return the position of the header.
+    ///
+    fn find_head(&mut self, start: usize, window: usize) -> Result<Option<usize>> {
+        self.position(start)?;
+
+        let mut bytes = vec![];
+        self.take(window as u64 + 10).read_to_end(&mut bytes)?;
+
+        let mut position = 0;
+        while position + 10 <= bytes.len() {
+            if is_head(&bytes[position..position + 10]) {
+                return Ok(Some(start + position));
+            }
+            position = position + 1;
+        }
+
+        Ok(None)
+    }
 
-        Ok(Unit::ExtendedHeader(self.read_bytes(size as usize)?))
+    ///
+    /// it read extended header that locate at first of the frame bytes.
+    /// If tag version is 4, the size of extened header is calcurated as synchsize.
+    ///
+    fn ext_head(&mut self, head: &Head, readable: &mut Cursor<Vec<u8>>) -> Result<Unit> {
+        Ok(Unit::ExtendedHeader(ExtendedHeader::read(readable, head.version)?))
     }
 
     ///
@@ -70,6 +249,19 @@ pub trait ReadOp: Readable {
     /// if flag of header unsynchronized, it recompute to synchronized byte.
     ///
     fn frame_bytes(&mut self, head: &Head) -> Result<Cursor<Vec<u8>>> {
+        self.frame_bytes_with(head, &ParseOptions::default())
+    }
+
+    ///
+    /// It return a Readable that have all the frame bytes with the options.
+    /// the size of the tag must not be larger than `Limits::max_tag_size`.
+    ///
+    fn frame_bytes_with(&mut self,
+                        head: &Head,
+                        options: &ParseOptions)
+                        -> Result<Cursor<Vec<u8>>> {
+        check_limit("Tag size", head.size as usize, options.limits.max_tag_size)?;
+
         if head.has_flag(HeadFlag::Unsynchronisation) {
             Ok(Cursor::new(self.to_synchronize(head.size as usize)?))
         } else {
@@ -80,14 +272,58 @@ pub trait ReadOp: Readable {
     ///
     /// read a version 2.x
     ///
-    fn frame(&mut self, head: &Head, readable_wrap: FrameReadable) -> Result<Unit> {
-        let mut readable = readable_wrap.borrow_mut();
+    fn frame(&mut self, head: &Head, readable: &mut Cursor<Vec<u8>>) -> Result<Unit> {
+        self.frame_with(head, readable, &ParseOptions::default(), &mut Violations::default())
+    }
 
+    ///
+    /// read a version 2.x with the options. the spec violations are checked by 'violations'.
+    ///
+    fn frame_with(&mut self,
+                  head: &Head,
+                  readable: &mut Cursor<Vec<u8>>,
+                  options: &ParseOptions,
+                  violations: &mut Violations)
+                  -> Result<Unit> {
         match head.version {
-            2 => self.frame2(&mut readable),
-            3 => self.frame3(&mut readable),
-            _ => self.frame4(&mut readable),
+            2 => self.frame2_with(readable, options, violations),
+            3 => self.frame3_with(readable, options, violations),
+            _ => self.frame4_with(readable, options, violations),
+        }
+    }
+
+    ///
+    /// it read remain bytes of the frame bytes.
+    /// @tag_end: the position of the end of the tag in the source.
+    ///
+    fn padding(&mut self, tag_end: usize, readable: &mut Cursor<Vec<u8>>) -> Result<Unit> {
+        let bytes = readable.all_bytes()?;
+        let all_zero = bytes.iter().all(|b| *b == 0);
+
+        Ok(Unit::Padding {
+            offset: tag_end - bytes.len(),
+            size: bytes.len(),
+            all_zero: all_zero,
+            bytes: if all_zero { Vec::new() } else { bytes },
+        })
+    }
+
+    ///
+    /// it read footer that is 10 byte length and follow the frames.
+    /// the footer must be same with the header except the identifier.
+    ///
+    fn footer(&mut self, head: &Head) -> Result<Unit> {
+        let footer = Head::read(&mut self.to_readable(10)?, 4, "")?;
+
+        if footer.tag_id != "3DI" || footer.version != head.version ||
+           footer.minor_version != head.minor_version || footer.flag != head.flag ||
+           footer.size != head.size {
+            let err_msg = "Invalid footer";
+            warn!("{}: {:?}", err_msg, footer);
+            return Err(Id3Error::new(error::ErrorKind::InvalidHeader, err_msg));
         }
+
+        Ok(Unit::Footer(footer))
     }
 
     ///
@@ -102,7 +338,7 @@ pub trait ReadOp: Readable {
         if file_len < 128 {
             let err_msg = "Invalid frame1 length";
             warn!("{}", err_msg);
-            return Err(Error::new(ErrorKind::Other, err_msg));
+            return Err(Id3Error::new(error::ErrorKind::InvalidHeader, err_msg));
         }
 
         //
@@ -116,7 +352,7 @@ pub trait ReadOp: Readable {
         if self.look_string(3)? != "TAG" {
             let err_msg = "Invalid frame1 id";
             warn!("{}: {:?}", err_msg, self.all_bytes());
-            return Err(Error::new(ErrorKind::Other, err_msg));
+            return Err(Id3Error::new(error::ErrorKind::InvalidHeader, err_msg));
         }
 
         Ok(Unit::FrameV1(Frame1::read(&mut self.to_readable(128)?)?))
@@ -126,168 +362,202 @@ pub trait ReadOp: Readable {
     /// read a version 2.2
     ///
     fn frame2(&mut self, readable: &mut Cursor<Vec<u8>>) -> Result<Unit> {
-        let frame_header = FrameHeaderV2::read(readable, 2, "")?;
-        let size = frame_header.size as usize;
-
-        let frame_body = match frame_header.has_flag(FrameHeaderFlag::Encryption) {
-            true => FrameBody::SKIP(frame_header.id.to_owned(), readable.read_bytes(size)?),
-            false => {
-                read_framebody_with_id(frame_header.id.as_str(), 2, readable.to_readable(size)?)?
-            }
-        };
+        self.frame2_with(readable, &ParseOptions::default(), &mut Violations::default())
+    }
 
-        Ok(Unit::FrameV2(FrameHeader::V22(frame_header), frame_body))
+    ///
+    /// read a version 2.2 with the options.
+    ///
+    fn frame2_with(&mut self,
+                   readable: &mut Cursor<Vec<u8>>,
+                   options: &ParseOptions,
+                   violations: &mut Violations)
+                   -> Result<Unit> {
+        let frame = LazyFrame::read_with(readable, 2, options)?;
+        let frame_body = frame.decode_with_violations(options, violations)?;
+
+        Ok(Unit::FrameV2(frame.header, frame_body))
     }
 
     ///
     /// read a version 2.3
     ///
     fn frame3(&mut self, readable: &mut Cursor<Vec<u8>>) -> Result<Unit> {
+        self.frame3_with(readable, &ParseOptions::default(), &mut Violations::default())
+    }
 
-        let frame_header = FrameHeaderV3::read(readable, 3, "")?;
-
-        let mut extra_size: u32 = 0;
-
-        //
-        // If the flag of group-identity is set, one byte follow the frame size.
-        //
-        if frame_header.has_flag(FrameHeaderFlag::GroupIdentity) {
-            let _ = readable.read_u8()?;
-            extra_size = extra_size + 1;
-        }
-
-        //
-        // If the flag of encyrption is set, one byte follow the frame size.
-        //
-        if frame_header.has_flag(FrameHeaderFlag::Encryption) {
-            let _ = readable.read_u8()?;
-            extra_size = extra_size + 1;
-        }
-
-        //
-        // If the flag of compression is set, four byte follow the frame size.
-        //
-        let body_bytes = match frame_header.has_flag(FrameHeaderFlag::Compression) {
-            true => {
-                debug!("compression");
-
-                let _ = readable.read_u32()?;
-                extra_size = extra_size + 4;
-
-                let actual_size = frame_header.size - extra_size as u32;
-                let body_bytes = readable.read_bytes(actual_size as usize)?;
-
-                //
-                // The compression frame is compressed using zlip.
-                //
-                let mut decoder = ZlibDecoder::new(&body_bytes[..]);
-
-                let mut out = vec![];
-                let _ = decoder.read_to_end(&mut out);
-
-                out
-            }
-            false => {
-                let actual_size = frame_header.size - extra_size as u32;
-                readable.read_bytes(actual_size as usize)?
-            }
-        };
-
-
-        //
-        // If frame is encrypted, this frame can not read.
-        //
-        let frame_body = match frame_header.has_flag(FrameHeaderFlag::Encryption) {
-            true => {
-                debug!("encryption");
-                FrameBody::SKIP(frame_header.id.to_owned(), body_bytes)
-            }
-            false => read_framebody_with_id(frame_header.id.as_str(), 3, Cursor::new(body_bytes))?,
-        };
-
-        Ok(Unit::FrameV2(FrameHeader::V23(frame_header), frame_body))
+    ///
+    /// read a version 2.3 with the options.
+    ///
+    fn frame3_with(&mut self,
+                   readable: &mut Cursor<Vec<u8>>,
+                   options: &ParseOptions,
+                   violations: &mut Violations)
+                   -> Result<Unit> {
+        let frame = LazyFrame::read_with(readable, 3, options)?;
+        let frame_body = frame.decode_with_violations(options, violations)?;
+
+        Ok(Unit::FrameV2(frame.header, frame_body))
     }
 
     ///
     /// read a version 2.4
     ///
     fn frame4(&mut self, readable: &mut Cursor<Vec<u8>>) -> Result<Unit> {
-        let frame_header = FrameHeaderV4::read(readable, 4, "")?;
-
-        let mut extra_size: u32 = 0;
-
-        //
-        // If the flag of group-identity is set, one byte follow the frame size.
-        //
-        if frame_header.has_flag(FrameHeaderFlag::GroupIdentity) {
-            let _ = readable.read_u8()?;
-            extra_size = extra_size + 1;
-        }
-
-        //
-        // If the flag of encyrption is set, one byte follow the frame size.
-        //
-        if frame_header.has_flag(FrameHeaderFlag::Encryption) {
-            let _ = readable.read_u8()?;
-            extra_size = extra_size + 1;
-        }
+        self.frame4_with(readable, &ParseOptions::default(), &mut Violations::default())
+    }
 
-        //
-        // If the flag of data-length is set, one byte follow the frame size.
-        //
-        if frame_header.has_flag(FrameHeaderFlag::DataLength) {
-            let _ = readable.read_u32()?;
-            extra_size = extra_size + 4;
-        }
+    ///
+    /// read a version 2.4 with the options.
+    ///
+    fn frame4_with(&mut self,
+                   readable: &mut Cursor<Vec<u8>>,
+                   options: &ParseOptions,
+                   violations: &mut Violations)
+                   -> Result<Unit> {
+        let frame = LazyFrame::read_with(readable, 4, options)?;
+        let frame_body = frame.decode_with_violations(options, violations)?;
+
+        Ok(Unit::FrameV2(frame.header, frame_body))
+    }
 
-        let actual_size = frame_header.size - extra_size as u32;
-        let mut body_bytes = readable.read_bytes(actual_size as usize)?;
+    ///
+    /// It skip a frame without reading the body.
+    ///
+    fn skip_frame(&mut self, head: &Head, readable: &mut Cursor<Vec<u8>>) -> Result<()> {
+        let header = read_frame_header(readable, head.version)?;
+        readable.skip_bytes(header.size() as isize)?;
 
-        //
-        // If frame is unsynchronized, it re-build to synchronized byte.
-        //
-        if frame_header.has_flag(FrameHeaderFlag::Unsynchronisation) {
-            debug!("'{}' is unsynchronised", frame_header.id);
-            let bytes = body_bytes[..].to_vec();
-            body_bytes = Cursor::new(bytes).to_synchronize(body_bytes.len())?;
-        }
+        Ok(())
+    }
 
-        if frame_header.has_flag(FrameHeaderFlag::Compression) {
-            debug!("'{}' is compressed", frame_header.id);
+    ///
+    /// read a version 2.x but the frame body is not decoded.
+    ///
+    fn lazy_frame(&mut self, head: &Head, readable: &mut Cursor<Vec<u8>>) -> Result<Unit> {
+        self.lazy_frame_with(head, readable, &ParseOptions::default())
+    }
 
-            let real_frame = body_bytes.clone();
-            let mut out = vec![];
-            //
-            // The compression frame is compressed using zlip.
-            //
-            let mut decoder = ZlibDecoder::new(&real_frame[..]);
-            let _ = decoder.read_to_end(&mut out);
-            body_bytes = out;
-        }
+    ///
+    /// read a version 2.x with the options but the frame body is not decoded.
+    ///
+    fn lazy_frame_with(&mut self,
+                       head: &Head,
+                       readable: &mut Cursor<Vec<u8>>,
+                       options: &ParseOptions)
+                       -> Result<Unit> {
+        Ok(Unit::LazyFrameV2(LazyFrame::read_with(readable, head.version, options)?))
+    }
+}
 
-        //
-        // If frame is encrypted, this frame can not read.
-        //
-        let frame_body = match frame_header.has_flag(FrameHeaderFlag::Encryption) {
-            true => FrameBody::SKIP(frame_header.id.to_owned(), body_bytes),
-            false => read_framebody_with_id(frame_header.id.as_str(), 4, Cursor::new(body_bytes))?,
-        };
+///
+/// Apply 'ReadOp' to all the 'Readable'. e.g) 'File', 'Cursor'
+///
+impl<T> ReadOp for T where T: Readable {}
 
-        Ok(Unit::FrameV2(FrameHeader::V24(frame_header), frame_body))
-    }
+///
+/// Options of parsing.
+///
+/// - strict: If it is true, any spec violation is a error. e.g) a text that can not be decoded,
+///   an unknown frame id or junk in the padding.
+///   if it is false, it read as much as possible like before. (default)
+/// - resync: If it is true, after a frame that can not be read, it find a next frame in the tag
+///   and continue from there. the skipped bytes are reported as a diagnostic.
+/// - search_window: the number of bytes that is searched for the first header.
+///   if it is 0, the header must be at the first. (default)
+/// - lazy: If it is true, the frames are not decoded and `Unit::LazyFrameV2` is yield instead of
+///   `Unit::FrameV2`. the body is decoded by `LazyFrame::decode` when it is needed.
+/// - limits: the limits of the sizes for an untrusted input. there is no limit by default.
+///
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    pub strict: bool,
+    pub resync: bool,
+    pub search_window: usize,
+    pub lazy: bool,
+    pub limits: Limits,
 }
 
 ///
-/// Apply 'ReadOf' to 'File'.
+/// The limits of the sizes. if a limit is exceeded, it is a `ErrorKind::LimitExceeded` error.
 ///
-impl ReadOp for File {}
+/// - max_tag_size: the size of a tag that is declared in the header.
+/// - max_frame_size: the size of a frame that is declared in the frame header.
+/// - max_decompressed_size: the size of a compressed frame after it is decompressed.
+///   the decompressed size of 2.3 is checked before it is decompressed.
+/// - max_frames: the number of the frames in a tag. the rest of the tag is ignored.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limits {
+    pub max_tag_size: usize,
+    pub max_frame_size: usize,
+    pub max_decompressed_size: usize,
+    pub max_frames: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_tag_size: usize::max_value(),
+            max_frame_size: usize::max_value(),
+            max_decompressed_size: usize::max_value(),
+            max_frames: usize::max_value(),
+        }
+    }
+}
 
 ///
 /// Mp3 metadata reader.
 ///
-pub struct MetadataReader {
+/// The source can be any `Read + Seek`. if it is not given, it is a `File`.
+/// It is `Send` if the source is `Send`, so it can be moved to another thread.
+///
+pub struct MetadataReader<R = File> {
     next: Status,
-    file: File,
+    readable: R,
+    //
+    // The frame bytes of the current tag. it is synchronised if the tag is unsynchronised.
+    //
+    frame_readable: Cursor<Vec<u8>>,
+    //
+    // The position of the current tag.
+    //
+    offset: usize,
+    //
+    // The positions of the tags that already read.
+    //
+    tags: Vec<usize>,
+    //
+    // The position of the next tag that a SEEK frame point to.
+    //
+    seek: Option<usize>,
+    //
+    // If it is true, the appended tag is already searched.
+    //
+    appended: bool,
+    options: ParseOptions,
+    diagnostics: Vec<Diagnostic>,
+    //
+    // If it is set, only these frames are read.
+    //
+    frame_ids: Option<Vec<String>>,
+    //
+    // The positions of the headers that are read.
+    //
+    tag_offsets: Vec<usize>,
+    //
+    // The end of the current tag. a next tag can start from there.
+    //
+    tag_end: Option<usize>,
+    //
+    // The number of the frames that are read in the current tag.
+    //
+    frame_count: usize,
+    decryptors: Vec<(DecryptorKey, Box<dyn FrameDecryptor>)>,
+    //
+    // The ENCR frames of the current tag. they are read only if a decryptor is registered.
+    //
+    encryptions: Vec<ENCR>,
 }
 
 impl MetadataReader {
@@ -296,61 +566,137 @@ impl MetadataReader {
     /// @path: a file path.
     ///
     pub fn new(path: &str) -> Result<Self> {
+        MetadataReader::from_reader(File::open(path)?)
+    }
+}
+
+impl<'a> MetadataReader<Cursor<&'a [u8]>> {
+    ///
+    /// It create a new MetadataReader from bytes in memory.
+    /// @bytes: whole bytes of a mp3. e.g) `&vec[..]`
+    ///
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self> {
+        MetadataReader::from_reader(Cursor::new(bytes))
+    }
+}
+
+impl<R> MetadataReader<R>
+    where R: Read + Seek
+{
+    ///
+    /// It create a new MetadataReader from a `Read + Seek` source.
+    /// @readable: a source that have all the bytes of a mp3.
+    ///
+    pub fn from_reader(readable: R) -> Result<Self> {
         Ok(MetadataReader {
             next: Status::None,
-            file: File::open(path)?,
+            readable: readable,
+            frame_readable: Cursor::new(Vec::new()),
+            offset: 0,
+            tags: Vec::new(),
+            seek: None,
+            appended: false,
+            options: ParseOptions::default(),
+            diagnostics: Vec::new(),
+            frame_ids: None,
+            tag_offsets: Vec::new(),
+            tag_end: None,
+            frame_count: 0,
+            decryptors: Vec::new(),
+            encryptions: Vec::new(),
         })
     }
 
+    ///
+    /// The start offsets of the tags that are read until now.
+    /// it is in the order of `Unit::Header`.
+    ///
+    pub fn tag_offsets(&self) -> &[usize] {
+        &self.tag_offsets
+    }
+
+    ///
+    /// It read only the frames that have the given ids. e.g) `&[id::TIT2, id::TPE1]`
+    /// The other frames are skipped without reading the body.
+    /// In 2.2, the id of 2.4 is also matched. e.g) 'TT2' is matched with `id::TIT2`.
+    ///
+    pub fn with_frame_ids(mut self, ids: &[&str]) -> Self {
+        self.frame_ids = Some(ids.iter().map(|id| id.to_string()).collect());
+        self
+    }
+
+    ///
+    /// The problems that are found until now.
+    /// The errors that `try_iter` yield or the Iterator skip and the recovered problems.
+    ///
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    ///
+    /// It set the options of parsing.
+    ///
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    ///
+    /// It register a decryptor for the ENCR frames of the owner identifier or the method symbol.
+    ///
+    /// A encrypted frame is decrypted if its method symbol is of a ENCR frame in the same tag
+    /// and a decryptor is registered for the ENCR frame. the decrypted bytes are read with
+    /// the same way of the other frames. if not, it is `FrameBody::SKIP`.
+    /// `Unit::LazyFrameV2` is not decrypted.
+    ///
+    pub fn with_decryptor<D>(mut self, key: DecryptorKey, decryptor: D) -> Self
+        where D: FrameDecryptor + 'static
+    {
+        self.decryptors.push((key, Box::new(decryptor)));
+        self
+    }
+
     //
-    // It decide next unit that follow a head unit.
-    // - if extended header exist, next is extended header. if not, next is frames.
+    // It find a position of the next tag. it starts where the current tag ends, is pointed by
+    // a SEEK frame or is located by the footer.
     //
-    fn set_head_next(&mut self, header: &Unit) {
-        if let &Unit::Header(ref head) = header {
-            let head = head.clone();
+    fn next_tag_offset(&mut self) -> Option<usize> {
+        if let Some(offset) = self.tag_end.take() {
+            if !self.tags.contains(&offset) {
+                if let Ok(Some(offset)) = self.readable.find_head(offset, 0) {
+                    return Some(offset);
+                }
+            }
+        }
 
-            if head.has_flag(HeadFlag::ExtendedHeader) {
-                self.next = Status::ExtendedHeader(head);
-                return;
+        if let Some(offset) = self.seek.take() {
+            if !self.tags.contains(&offset) {
+                return Some(offset);
             }
+        }
 
-            self.next = match self.file.frame_bytes(&head) {
-                Err(_) => Status::Error,
-                Ok(readable) => {
-                    let r = Rc::new(RefCell::new(Box::new(readable)));
-                    Status::Frame(head, r)
+        if !self.appended {
+            self.appended = true;
+
+            if let Ok(Some(offset)) = self.readable.appended_tag() {
+                if !self.tags.contains(&offset) {
+                    return Some(offset);
                 }
-            };
-        } else {
-            self.next = Status::Error;
+            }
         }
-    }
 
-    //
-    // The next unit of a extend head is a frame.
-    //
-    fn set_ext_head_next(&mut self, head: &Head) {
-        self.next = match self.file.frame_bytes(&head) {
-            Err(_) => Status::Error,
-            Ok(readable) => {
-                let r = Rc::new(RefCell::new(Box::new(readable)));
-                Status::Frame(head.clone(), r)
-            }
-        };
+        None
     }
 
     //
-    // If frame id exist, read next frame. if does not exist, read the frame1.
+    // If frame id exist, read next frame. if does not exist, read the padding, the footer or the
+    // frame1.
     //
-    fn set_frame_next(&mut self, head: &Head, readable_wrap: FrameReadable) {
-        let ref_readable = readable_wrap.clone();
-        let mut readable = readable_wrap.borrow_mut();
-
+    fn set_frame_next(&mut self, head: &Head) {
         //
         // The rule of frame id.
         //
-        let frame_exist = match readable.look_string(4) {
+        let frame_exist = match self.frame_readable.look_string(4) {
             Ok(id) => {
                 //
                 // http://id3.org/id3v2.4.0-structure > 4. ID3v2 frame overview
@@ -364,142 +710,725 @@ impl MetadataReader {
         };
 
         if frame_exist {
-            self.next = Status::Frame(head.clone(), ref_readable);
+            self.next = Status::Frame(head.clone());
+        } else if (self.frame_readable.position() as usize) < self.frame_readable.get_ref().len() {
+            self.next = Status::Padding(head.clone());
         } else {
-            self.next = Status::Frame1;
+            self.set_tag_end_next(head);
         }
     }
-}
 
-pub struct MetadataWriter<'a> {
     //
-    // file path
+    // The end of tag is the footer or the next tag.
     //
-    path: &'a str,
-}
-
-impl<'a> MetadataWriter<'a> {
-    pub fn new(path: &'a str) -> Result<Self> {
-        Ok(MetadataWriter { path: path })
+    fn set_tag_end_next(&mut self, head: &Head) {
+        if head.has_flag(HeadFlag::FooterPresent) {
+            self.next = Status::Footer(head.clone());
+        } else {
+            self.next = Status::NextTag;
+        }
     }
+}
 
-    /// clean_write: it determin if rewrite all to version 4 or not. if it 'true', it rewrite to version 4.
-    /// and in 2.2 'CRM', 'PIC'. in 2.3 'EQUA', 'IPLS', 'RVAD', 'TDAT', 'TIME', 'TORY', 'TRDA', 'TSIZ',
-    /// 'TYER' frames are ignored.
-    ///
-    /// if it is false, it write with given 'units' parameter.
-    /// but it checks version. all of the unit must have to same version.
-    ///
-    /// if both 'head' are not given, a 'head' will be created with version 4.
-    pub fn write(&self, mut units: Vec<Unit>, clean_write: bool) -> Result<()> {
-        if clean_write {
-            units = self.fix_units(&units)?;
-        } else {
-            self.check_version(&units)?;
+///
+/// The summary of the tags that is read only with the headers and the last 128 bytes.
+///
+/// - id3v2: if the tag at the first of a file exist.
+/// - tag_offset: the position of the first header. it is not 0 if the header is found after
+///   junk bytes in `ParseOptions::search_window`.
+/// - minor_version: the revision. e.g) 0 in 2.4.0
+/// - size: the size of the tag that is declared in the header.
+/// - audio_offset: the position that the audio starts. it is after the tags that are stacked
+///   after the first one. it is 0 if there is no tag.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProbeInfo {
+    pub id3v2: bool,
+    pub tag_offset: usize,
+    pub version: u8,
+    pub minor_version: u8,
+    pub flag: u8,
+    pub size: u32,
+    pub audio_offset: usize,
+    pub id3v1: bool,
+}
+
+///
+/// It read the headers and the last 128 bytes of a file. the frames are not read.
+///
+pub fn probe(path: &str) -> Result<ProbeInfo> {
+    probe_reader(&mut File::open(path)?)
+}
+
+///
+/// Same with `probe`, but the first header is searched with `ParseOptions::search_window`.
+///
+pub fn probe_with(path: &str, options: &ParseOptions) -> Result<ProbeInfo> {
+    probe_reader_with(&mut File::open(path)?, options)
+}
+
+///
+/// It read the headers and the last 128 bytes of a `Read + Seek` source.
+///
+pub fn probe_reader<R>(readable: &mut R) -> Result<ProbeInfo>
+    where R: Read + Seek
+{
+    probe_reader_with(readable, &ParseOptions::default())
+}
+
+///
+/// It read the headers and the last 128 bytes of a `Read + Seek` source with the options.
+/// The tags are found with the same way of `MetadataReader`. the first header is searched in
+/// `ParseOptions::search_window`, and a tag is stacked if a header is just after the tag.
+///
+pub fn probe_reader_with<R>(readable: &mut R, options: &ParseOptions) -> Result<ProbeInfo>
+    where R: Read + Seek
+{
+    let file_len = readable.position_end(0)?;
+    let id3v1 = readable.frame1(file_len).is_ok();
+
+    let mut info = ProbeInfo {
+        id3v2: false,
+        tag_offset: 0,
+        version: 0,
+        minor_version: 0,
+        flag: 0,
+        size: 0,
+        audio_offset: 0,
+        id3v1: id3v1,
+    };
+
+    let mut next = readable.find_head(0, options.search_window)?;
+    while let Some(offset) = next {
+        readable.position(offset)?;
+        let head = match readable.head() {
+            Ok(Unit::Header(head)) => head,
+            _ => break,
+        };
+
+        let mut tag_end = offset + 10 + head.size as usize;
+        if head.has_flag(HeadFlag::FooterPresent) {
+            tag_end = tag_end + 10;
         }
 
-        let (has_frame1, head_len, all_bytes) = self.to_bytes(units)?;
-        let (orig_head_len, file_len, orig_frame1_exist) = self.metadata_length()?;
+        if !info.id3v2 {
+            info.id3v2 = true;
+            info.tag_offset = offset;
+            info.version = head.version;
+            info.minor_version = head.minor_version;
+            info.flag = head.flag;
+            info.size = head.size;
+        }
+        info.audio_offset = tag_end;
 
-        let mut writable = OpenOptions::new().read(true)
-            .write(true)
-            .open(self.path)?;
+        next = readable.find_head(tag_end, 0)?;
+    }
 
-        let head_diff_len = orig_head_len as i32 - head_len as i32;
+    Ok(info)
+}
 
-        debug!("frame1: {}, head: {}, original head length: {}, original file len: {}, head \
-                diff: {}, frame1 exist: {}, clean write: {}",
-               has_frame1,
-               head_len,
-               orig_head_len,
-               file_len,
-               head_diff_len,
-               orig_frame1_exist,
-               clean_write);
+///
+/// The kind of a `Region`. the frame id is kept for the parts of a frame.
+///
+/// - GroupIdentity, EncryptionMethod: one byte that follow the frame header.
+/// - DataLength: four bytes of the data length indicator in 2.4, or of the decompressed size
+///   in 2.3.
+/// - Audio: the bytes between the tags and the frame1.
+/// - Overrun: the bytes that the size of a tag or a frame claims after the end of the file or
+///   the tag. they do not exist, and the offset is the end.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RegionKind {
+    Header,
+    ExtendedHeader,
+    FrameHeader(String),
+    GroupIdentity(String),
+    EncryptionMethod(String),
+    DataLength(String),
+    FrameBody(String),
+    Padding,
+    Footer,
+    FrameV1,
+    Audio,
+    Overrun,
+}
 
-        //
-        // when new metadata size is shorter than original size.
-        //
-        if head_diff_len > 0 && file_len > head_diff_len as u64 {
-            debug!("Head unshift");
+///
+/// The byte span of a structure in a file.
+///
+/// - file_offset, file_size: the span in the file. if the tag is unsynchronised, the bytes
+///   that is inserted by the unsynchronisation are included.
+/// - tag_offset, tag_size: the span in the synchronised bytes that follow the header, which are
+///   built by `ReadOp::frame_bytes`. it is `None` for the header, the footer, the frame1 and
+///   the audio.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Region {
+    pub kind: RegionKind,
+    pub file_offset: usize,
+    pub file_size: usize,
+    pub tag_offset: Option<usize>,
+    pub tag_size: Option<usize>,
+}
+
+///
+/// It return the spans of all the structures of a file in the order of the position.
+///
+pub fn layout(path: &str) -> Result<Vec<Region>> {
+    layout_reader(&mut File::open(path)?)
+}
+
+///
+/// It return the spans with the options. the size of a tag must not be larger than
+/// `Limits::max_tag_size`.
+///
+pub fn layout_with(path: &str, options: &ParseOptions) -> Result<Vec<Region>> {
+    layout_reader_with(&mut File::open(path)?, options)
+}
+
+///
+/// It return the spans of all the structures of a `Read + Seek` source.
+/// the tags that are stacked at the first, the tag that is located by the footer and the tags
+/// that SEEK frames point to are read. the frame bodies are not decoded.
+/// a size that overruns the file is marked by a `RegionKind::Overrun` instead of an error.
+///
+pub fn layout_reader<R>(readable: &mut R) -> Result<Vec<Region>>
+    where R: Read + Seek
+{
+    layout_reader_with(readable, &ParseOptions::default())
+}
 
-            writable.unshift(head_diff_len as usize)?;
+///
+/// It return the spans of a `Read + Seek` source with the options.
+///
+pub fn layout_reader_with<R>(readable: &mut R, options: &ParseOptions) -> Result<Vec<Region>>
+    where R: Read + Seek
+{
+    let file_len = readable.position_end(0)?;
+    let id3v1 = readable.frame1(file_len).is_ok();
+    let audio_end = if id3v1 { file_len - 128 } else { file_len };
+
+    let mut regions = Vec::new();
+    let mut offset = 0;
+
+    let mut next_tags = Vec::new();
+    if let Ok(Some(appended)) = readable.appended_tag() {
+        next_tags.push(appended);
+    }
 
-            let mut len = file_len - head_diff_len as u64;
-            if clean_write && orig_frame1_exist {
-                len = len - 128;
+    loop {
+        while offset + 10 <= audio_end {
+            match tag_layout(readable, offset, audio_end, options)? {
+                Some((tag_regions, tag_end, seek)) => {
+                    regions.extend(tag_regions);
+                    next_tags.extend(seek);
+                    offset = tag_end;
+                }
+                None => break,
             }
-            OpenOptions::new().write(true).open(self.path)?.set_len(len)?;
         }
+
         //
-        // when new metadata size is larger than original size.
+        // The next tag is after the audio. the tags that are already passed are ignored.
         //
-        else if head_diff_len < 0 && file_len > head_diff_len.abs() as u64 {
-            debug!("Head shift");
+        next_tags.retain(|next_tag| *next_tag >= offset && *next_tag + 10 <= audio_end);
+        next_tags.sort();
+
+        let next_tag = match next_tags.first() {
+            Some(next_tag) => *next_tag,
+            None => break,
+        };
+        next_tags.remove(0);
+
+        if offset < next_tag {
+            regions.push(Region {
+                kind: RegionKind::Audio,
+                file_offset: offset,
+                file_size: next_tag - offset,
+                tag_offset: None,
+                tag_size: None,
+            });
+        }
+
+        offset = next_tag;
+    }
+
+    if offset < audio_end {
+        regions.push(Region {
+            kind: RegionKind::Audio,
+            file_offset: offset,
+            file_size: audio_end - offset,
+            tag_offset: None,
+            tag_size: None,
+        });
+    }
+
+    if id3v1 {
+        regions.push(Region {
+            kind: RegionKind::FrameV1,
+            file_offset: audio_end,
+            file_size: 128,
+            tag_offset: None,
+            tag_size: None,
+        });
+    }
+
+    Ok(regions)
+}
+
+//
+// It return the spans of a tag at 'offset', the end of the tag and the position of the tag
+// that a SEEK frame point to. if there is no tag at 'offset', it return `None`.
+//
+fn tag_layout<R>(readable: &mut R,
+                 offset: usize,
+                 audio_end: usize,
+                 options: &ParseOptions)
+                 -> Result<Option<(Vec<Region>, usize, Option<usize>)>>
+    where R: Read + Seek
+{
+    readable.position(offset)?;
+    let mut head = match readable.head() {
+        Ok(Unit::Header(head)) => head,
+        _ => return Ok(None),
+    };
+
+    let region = |kind, file_offset, file_size| {
+        Region {
+            kind: kind,
+            file_offset: file_offset,
+            file_size: file_size,
+            tag_offset: None,
+            tag_size: None,
+        }
+    };
+
+    let mut regions = vec![region(RegionKind::Header, offset, 10)];
+
+    let tag_start = offset + 10;
+    let mut tag_end = tag_start + head.size as usize;
+    if head.has_flag(HeadFlag::FooterPresent) {
+        tag_end = tag_end + 10;
+    }
+
+    //
+    // The tag size overruns the file. the bytes that exist are read as the tag.
+    //
+    if tag_end > audio_end {
+        warn!("Invalid tag size: {}", head.size);
+
+        head.size = (audio_end - tag_start) as u32;
+        regions.extend(tag_regions(readable, &head, tag_start, options)?);
+        regions.push(region(RegionKind::Overrun, audio_end, tag_end - audio_end));
+
+        return Ok(Some((regions, audio_end, None)));
+    }
+
+    regions.extend(tag_regions(readable, &head, tag_start, options)?);
+
+    if head.has_flag(HeadFlag::FooterPresent) {
+        regions.push(region(RegionKind::Footer, tag_end - 10, 10));
+    }
+
+    //
+    // The offset of a SEEK frame is from the end of the tag.
+    //
+    let mut seek = None;
+    for region in regions.iter() {
+        if region.kind == RegionKind::FrameBody(id::SEEK.to_string()) && region.file_size == 4 {
+            readable.position(region.file_offset)?;
+            seek = Some(tag_end + readable.read_u32()? as usize);
+        }
+    }
+
+    Ok(Some((regions, tag_end, seek)))
+}
+
+//
+// It return the spans of the extended header, the frames and the padding of a tag.
+// the position of 'readable' is at the first of the frame bytes.
+//
+fn tag_regions<R>(readable: &mut R,
+                  head: &Head,
+                  tag_start: usize,
+                  options: &ParseOptions)
+                  -> Result<Vec<Region>>
+    where R: Read + Seek
+{
+    let bytes = readable.frame_bytes_with(head, options)?.into_inner();
+
+    //
+    // The position in the file of each synchronised byte. the last one is the end of the tag.
+    //
+    let mut file_offsets = Vec::with_capacity(bytes.len() + 1);
+    if head.has_flag(HeadFlag::Unsynchronisation) {
+        readable.position(tag_start)?;
+        let raw = readable.read_bytes(head.size as usize)?;
+
+        let mut copy = true;
+        for i in 0..raw.len() {
+            if copy || raw[i] != 0 {
+                file_offsets.push(tag_start + i);
+            }
+            copy = raw[i] != 0xff;
+        }
+        file_offsets.push(tag_start + raw.len());
+    } else {
+        file_offsets.extend((0..bytes.len() + 1).map(|i| tag_start + i));
+    }
+
+    let mut regions = Vec::new();
+    let mut push = |kind, start: usize, size: usize| {
+        regions.push(Region {
+            kind: kind,
+            file_offset: file_offsets[start],
+            file_size: file_offsets[start + size] - file_offsets[start],
+            tag_offset: Some(start),
+            tag_size: Some(size),
+        });
+    };
+
+    let len = bytes.len();
+    let mut readable = Cursor::new(bytes);
+
+    if head.has_flag(HeadFlag::ExtendedHeader) {
+        match ExtendedHeader::read(&mut readable, head.version) {
+            Ok(_) => push(RegionKind::ExtendedHeader, 0, readable.position() as usize),
+            Err(_) => {
+                push(RegionKind::Padding, 0, len);
+                return Ok(regions);
+            }
+        }
+    }
+
+    let (header_len, id_len) = if head.version == 2 { (6, 3) } else { (10, 4) };
+
+    loop {
+        let start = readable.position() as usize;
+        if start >= len {
+            break;
+        }
+
+        let frame_exist = start + id_len <= len &&
+                          is_frame_id(&readable.get_ref()[start..start + id_len]);
+
+        let header = match frame_exist {
+            true => read_frame_header_unchecked(&mut readable, head.version).ok(),
+            false => None,
+        };
+
+        let header = match header {
+            Some(header) => header,
+            None => {
+                push(RegionKind::Padding, start, len - start);
+                break;
+            }
+        };
+
+        let id = header.id();
+        push(RegionKind::FrameHeader(id.clone()), start, header_len);
+
+        let mut body_start = start + header_len;
+        let frame_end = body_start + header.size() as usize;
+        let body_end = if frame_end > len { len } else { frame_end };
 
-            let diff = head_diff_len.abs() as usize;
-            writable.shift(diff)?;
+        let mut extra = Vec::new();
+        if header.has_flag(FrameHeaderFlag::GroupIdentity) {
+            extra.push((RegionKind::GroupIdentity(id.clone()), 1));
+        }
+        if header.has_flag(FrameHeaderFlag::Encryption) {
+            extra.push((RegionKind::EncryptionMethod(id.clone()), 1));
+        }
+        if (head.version == 3 && header.has_flag(FrameHeaderFlag::Compression)) ||
+           (head.version == 4 && header.has_flag(FrameHeaderFlag::DataLength)) {
+            extra.push((RegionKind::DataLength(id.clone()), 4));
+        }
 
-            if clean_write && orig_frame1_exist {
-                let len = file_len + diff as u64 - 128 as u64;
-                OpenOptions::new().write(true).open(self.path)?.set_len(len)?;
+        for (kind, size) in extra {
+            if body_start + size > body_end {
+                break;
             }
+            push(kind, body_start, size);
+            body_start = body_start + size;
         }
+
+        push(RegionKind::FrameBody(id), body_start, body_end - body_start);
+
         //
-        // header size is same but clean option is true.
+        // The frame size overruns the tag.
         //
-        else if clean_write && orig_frame1_exist {
-            let len = file_len - 128 as u64;
-            OpenOptions::new().write(true).open(self.path)?.set_len(len)?;
+        if frame_end > len {
+            regions.push(Region {
+                kind: RegionKind::Overrun,
+                file_offset: file_offsets[len],
+                file_size: frame_end - len,
+                tag_offset: Some(len),
+                tag_size: Some(frame_end - len),
+            });
+            break;
         }
 
-        let (head_bytes, frames) = all_bytes.split_at(head_len as usize);
+        readable.set_position(body_end as u64);
+    }
+
+    Ok(regions)
+}
+
+//
+// How `MetadataWriter` replace the tag at the first of a source.
+//
+// - tag_bytes: the new tag that is written at the first.
+// - frame1_bytes: the frame1 that is written at the last 128 bytes.
+// - audio_offset, audio_end: the audio of the source that is moved to the end of the new tag.
+//   the frame1 of the source is included in it unless it is a clean write.
+//
+pub(crate) struct TagRewrite {
+    pub tag_bytes: Vec<u8>,
+    pub frame1_bytes: Option<Vec<u8>>,
+    pub audio_offset: u64,
+    pub audio_end: u64,
+}
+
+impl TagRewrite {
+    //
+    // 'all_bytes' is the tag and the frame1 that `tag_bytes` return. 'info' and 'file_len' are
+    // of the original source.
+    // If the tag is found after junk bytes, it is a error. the new tag would be written before
+    // the junk and the old tag would be kept.
+    //
+    pub fn new(mut all_bytes: Vec<u8>,
+               has_frame1: bool,
+               clean_write: bool,
+               info: &ProbeInfo,
+               file_len: u64)
+               -> Result<Self> {
+        if info.tag_offset > 0 {
+            return Err(Id3Error::new(error::ErrorKind::InvalidHeader,
+                                     "The tag is not at the first of the source"));
+        }
 
-        let (frame_bytes, frame1_bytes) = if has_frame1 {
-            frames.split_at(frames.len() - 128)
+        let frame1_bytes = if has_frame1 {
+            let at = all_bytes.len() - 128;
+            Some(all_bytes.split_off(at))
         } else {
-            frames.split_at(frames.len())
+            None
         };
 
-        writable.write(&head_bytes)?;
-        writable.write(&frame_bytes)?;
+        let audio_offset = ::std::cmp::min(info.audio_offset as u64, file_len);
+        let mut audio_end = file_len;
+        if clean_write && info.id3v1 && audio_end >= audio_offset + 128 {
+            audio_end = audio_end - 128;
+        }
+
+        let len = all_bytes.len() as u64 + audio_end - audio_offset;
+
+        Ok(TagRewrite {
+            tag_bytes: all_bytes,
+            //
+            // The frame1 is written over the last 128 bytes only if it is not a clean write.
+            //
+            frame1_bytes: match frame1_bytes {
+                Some(bytes) if !clean_write && len >= 128 => Some(bytes),
+                _ => None,
+            },
+            audio_offset: audio_offset,
+            audio_end: audio_end,
+        })
+    }
+
+    //
+    // The length of the source after it is written.
+    //
+    pub fn len(&self) -> u64 {
+        self.tag_bytes.len() as u64 + self.audio_end - self.audio_offset
+    }
+
+    //
+    // It write to 'writable' that have the original bytes. the bytes after `len` are not
+    // removed, so the caller must truncate it.
+    //
+    pub fn apply<W>(&self, writable: &mut W) -> Result<()>
+        where W: Writable
+    {
+        let tag_len = self.tag_bytes.len() as u64;
+
+        if tag_len < self.audio_offset {
+            debug!("Head unshift");
+
+            writable.position(tag_len as usize)?;
+            writable.unshift((self.audio_offset - tag_len) as usize)?;
+        } else if tag_len > self.audio_offset {
+            debug!("Head shift");
+
+            writable.position(self.audio_offset as usize)?;
+            writable.shift((tag_len - self.audio_offset) as usize)?;
+        }
+
+        writable.position(0)?;
+        writable.write_all(&self.tag_bytes)?;
 
-        if !clean_write && has_frame1 {
-            writable.position_end(-128)?;
-            writable.write(&frame1_bytes)?;
+        if let Some(ref frame1_bytes) = self.frame1_bytes {
+            writable.position(self.len() as usize - 128)?;
+            writable.write_all(frame1_bytes)?;
         }
 
         Ok(())
     }
+}
+
+//
+// It return the bytes of the tag and the frame1 of 'units', and if the frame1 exist.
+// It does not need a path, so it is shared by `MetadataWriter` and `AsyncMetadataWriter`.
+//
+pub(crate) fn tag_bytes(mut units: Vec<Unit>,
+                        clean_write: bool,
+                        round_trip: bool)
+                        -> Result<(bool, Vec<u8>)> {
+    let writer = MetadataWriter {
+        path: None,
+        round_trip: round_trip,
+        options: ParseOptions::default(),
+    };
+
+    if !round_trip {
+        units = writer.decode_lazy_frames(units)?;
+    }
+
+    if clean_write {
+        units = writer.fix_units(&units)?;
+    } else {
+        writer.check_version(&units)?;
+    }
+
+    let (has_frame1, _, all_bytes) = writer.to_bytes(units)?;
+
+    Ok((has_frame1, all_bytes))
+}
+
+pub struct MetadataWriter<'a> {
+    //
+    // file path. it is `None` only in `tag_bytes` that does not write a file.
+    //
+    path: Option<&'a str>,
+    //
+    // If it is true, the lazy frames and the padding are written as they are read.
+    //
+    round_trip: bool,
+    //
+    // The options that the tag of the source is found with.
+    //
+    options: ParseOptions,
+}
+
+impl<'a> MetadataWriter<'a> {
+    pub fn new(path: &'a str) -> Result<Self> {
+        Ok(MetadataWriter {
+            path: Some(path),
+            round_trip: false,
+            options: ParseOptions::default(),
+        })
+    }
+
+    ///
+    /// The tag of the source is found with `ParseOptions::search_window` of 'options'.
+    /// If it is found after junk bytes, `write` is a error instead of writing another tag.
+    ///
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
 
     ///
-    /// @return tuple. (origin header size, origin file size, frame1 exist)
+    /// In round-trip mode, `Unit::LazyFrameV2` is written with the bytes that are read instead
+    /// of decoding and encoding it again, and `Unit::Padding` is written with the bytes that
+    /// are read. zero bytes if they are all zero.
+    /// So the units that are read with `ParseOptions { lazy: true, .. }` are written to the
+    /// same bytes of the tag if they are not changed. a changed frame is given as
+    /// `Unit::FrameV2`.
     ///
-    fn metadata_length(&self) -> Result<(u32, u64, bool)> {
+    pub fn with_round_trip(mut self, round_trip: bool) -> Self {
+        self.round_trip = round_trip;
+        self
+    }
 
-        let i = MetadataReader::new(self.path)
-            ?
-            .filter(|m| match m {
-                &Unit::Header(_) => true,
-                &Unit::FrameV1(_) => true,
-                _ => false,
-            })
-            .map(|unit| match unit {
-                Unit::Header(head) => head.size,
-                Unit::FrameV1(_) => 128,
-                _ => 0,
-            })
-            .collect::<Vec<_>>();
+    /// clean_write: it determin if rewrite all to version 4 or not. if it 'true', it rewrite to version 4.
+    /// and in 2.2 'CRM', 'PIC'. in 2.3 'EQUA', 'IPLS', 'RVAD', 'TDAT', 'TIME', 'TORY', 'TRDA', 'TSIZ',
+    /// 'TYER' frames are ignored.
+    ///
+    /// if it is false, it write with given 'units' parameter.
+    /// but it checks version. all of the unit must have to same version.
+    ///
+    /// if both 'head' are not given, a 'head' will be created with version 4.
+    pub fn write(&self, units: Vec<Unit>, clean_write: bool) -> Result<()> {
+        let (has_frame1, all_bytes) = tag_bytes(units, clean_write, self.round_trip)?;
 
-        let header_length = if i.len() > 0 { i[0] } else { 0 };
-        let frame1_exist = if i.len() > 1 && i[1] == 128 {
-            true
-        } else {
-            false
+        let path = match self.path {
+            Some(path) => path,
+            None => return Err(Id3Error::new(error::ErrorKind::Io, "No path to write")),
         };
-        let file_len = File::open(self.path)?.metadata()?.len();
 
-        Ok((header_length, file_len, frame1_exist))
+        //
+        // The tag at the first of a file and the tags that are stacked after it are rewritten.
+        // the appended tags are ignored.
+        //
+        let info = probe_with(path, &self.options)?;
+        let file_len = File::open(path)?.metadata()?.len();
+        let rewrite = TagRewrite::new(all_bytes, has_frame1, clean_write, &info, file_len)?;
+
+        let mut writable = OpenOptions::new().read(true)
+            .write(true)
+            .open(path)?;
+
+        rewrite.apply(&mut writable)?;
+        writable.set_len(rewrite.len())?;
+
+        Ok(())
+    }
+
+    ///
+    /// It return the bytes of a whole file that `write` would make from 'source'.
+    /// the path is not used. the arguments are same with `write`.
+    ///
+    pub fn to_file_bytes(&self,
+                         units: Vec<Unit>,
+                         clean_write: bool,
+                         source: &[u8])
+                         -> Result<Vec<u8>> {
+        let (has_frame1, all_bytes) = tag_bytes(units, clean_write, self.round_trip)?;
+
+        let info = probe_reader_with(&mut Cursor::new(source), &self.options)?;
+        let rewrite = TagRewrite::new(all_bytes,
+                                      has_frame1,
+                                      clean_write,
+                                      &info,
+                                      source.len() as u64)?;
+
+        let mut writable = Cursor::new(source.to_vec());
+        rewrite.apply(&mut writable)?;
+
+        let mut bytes = writable.into_inner();
+        bytes.truncate(rewrite.len() as usize);
+
+        Ok(bytes)
+    }
+
+    ///
+    /// It decode all the lazy frames to `Unit::FrameV2`.
+    ///
+    fn decode_lazy_frames(&self, units: Vec<Unit>) -> Result<Vec<Unit>> {
+        let mut ret = Vec::new();
+
+        for unit in units {
+            match unit {
+                Unit::LazyFrameV2(frame) => {
+                    let frame_body = frame.decode()?;
+                    ret.push(Unit::FrameV2(frame.header, frame_body));
+                }
+                _ => ret.push(unit),
+            }
+        }
+
+        Ok(ret)
     }
 
     ///
@@ -517,7 +1446,8 @@ impl<'a> MetadataWriter<'a> {
             _ => 4,
         };
 
-        let err = Err(Error::new(ErrorKind::InvalidData, "exist different version of 'Unit'"));
+        let err = Err(Id3Error::new(error::ErrorKind::VersionMismatch,
+                                    "exist different version of 'Unit'"));
 
         for unit in units {
             match unit {
@@ -530,6 +1460,9 @@ impl<'a> MetadataWriter<'a> {
                 &Unit::FrameV2(FrameHeader::V24(_), _) if head_version != 4 => {
                     return err;
                 }
+                &Unit::LazyFrameV2(ref frame) if frame.version() != head_version => {
+                    return err;
+                }
                 _ => (),
             }
         }
@@ -543,12 +1476,37 @@ impl<'a> MetadataWriter<'a> {
     pub fn fix_units(&self, units: &Vec<Unit>) -> Result<Vec<Unit>> {
         let ret = units.iter().fold(Vec::new(), |mut vec, unit| {
             match unit {
+                //
+                // A lazy frame is decoded first. if it can not be decoded, it is removed.
+                //
+                &Unit::LazyFrameV2(ref frame) => {
+                    if let Ok(frame_body) = frame.decode() {
+                        let frame = Unit::FrameV2(frame.header.clone(), frame_body);
+                        if let Ok(mut fixed) = self.fix_units(&vec![frame]) {
+                            vec.append(&mut fixed);
+                        }
+                    }
+                }
                 &Unit::Header(ref head) => {
                     let mut new_head = head.clone();
                     new_head.version = 4;
                     new_head.minor_version = 0;
                     vec.push(Unit::Header(new_head));
                 }
+                //
+                // 'padding size' of 2.3 is not in 2.4.
+                //
+                &Unit::ExtendedHeader(ref ext_head) => {
+                    vec.push(Unit::ExtendedHeader(ExtendedHeader {
+                        size: 0,
+                        flags: vec![0],
+                        padding_size: None,
+                        crc32: ext_head.crc32,
+                        tag_is_update: ext_head.tag_is_update,
+                        restrictions: ext_head.restrictions,
+                        extra: Vec::new(),
+                    }));
+                }
                 &Unit::FrameV2(ref frame_header, ref frame_body) => {
                     match frame_body {
                         &FrameBody::CRM(_) => (),
@@ -562,6 +1520,10 @@ impl<'a> MetadataWriter<'a> {
                         &FrameBody::TRDA(_) => (),
                         &FrameBody::TSIZ(_) => (),
                         &FrameBody::TYER(_) => (),
+                        //
+                        // The id of a unknown frame in 2.2 can not be converted.
+                        //
+                        &FrameBody::Unknown { .. } if frame_header.id().len() == 3 => (),
                         _ => {
                             let new_frame_header = match frame_header {
                                 &FrameHeader::V22(_) => {
@@ -571,6 +1533,9 @@ impl<'a> MetadataWriter<'a> {
                                         size: 0,
                                         status_flag: 0,
                                         encoding_flag: 0,
+                                        data_length: None,
+                                        group_symbol: None,
+                                        encryption_method: None,
                                     }
                                 }
                                 &FrameHeader::V23(ref header) => {
@@ -579,6 +1544,9 @@ impl<'a> MetadataWriter<'a> {
                                         size: 0,
                                         status_flag: 0,
                                         encoding_flag: 0,
+                                        data_length: None,
+                                        group_symbol: None,
+                                        encryption_method: None,
                                     };
 
                                     if header.has_flag(FrameHeaderFlag::TagAlter) {
@@ -599,6 +1567,8 @@ impl<'a> MetadataWriter<'a> {
                                     if header.has_flag(FrameHeaderFlag::GroupIdentity) {
                                         new_header.set_flag(FrameHeaderFlag::GroupIdentity);
                                     }
+                                    new_header.group_symbol = header.group_symbol;
+                                    new_header.encryption_method = header.encryption_method;
 
                                     new_header
                                 }
@@ -636,11 +1606,44 @@ impl<'a> MetadataWriter<'a> {
     }
 
     ///
-    /// It transform the Frame1 to byte array.
+    /// It transform the ExtendedHeader to byte array.
+    /// the CRC-32 is recalculated with 'frame_bytes' if it exist.
     ///
-    pub fn frame1(&self, frame1: Frame1) -> Result<Vec<u8>> {
+    pub fn ext_head(&self,
+                    version: u8,
+                    ext_head: ExtendedHeader,
+                    frame_bytes: &Vec<u8>)
+                    -> Result<Vec<u8>> {
+        //
+        // Padding is not written.
+        //
+        self.ext_head_with_padding(version, ext_head, frame_bytes, &[])
+    }
+
+    //
+    // The padding size of 2.3 is set to the length of 'padding'.
+    // The CRC of 2.3 is of the frames, and the one of 2.4 is of the frames and the padding.
+    //
+    fn ext_head_with_padding(&self,
+                             version: u8,
+                             mut ext_head: ExtendedHeader,
+                             frame_bytes: &Vec<u8>,
+                             padding: &[u8])
+                             -> Result<Vec<u8>> {
         let mut writable = Cursor::new(vec![0u8; 0]);
-        frame1.write(&mut writable)?;
+
+        if version == 3 {
+            ext_head.padding_size = Some(padding.len() as u32);
+        }
+
+        if ext_head.crc32.is_some() {
+            ext_head.crc32 = Some(match version {
+                4 => crc32(frame_bytes.iter().chain(padding.iter())),
+                _ => crc32(frame_bytes.iter()),
+            });
+        }
+
+        ext_head.write(&mut writable, version)?;
 
         let mut buf = Vec::new();
         writable.copy(&mut buf)?;
@@ -649,10 +1652,23 @@ impl<'a> MetadataWriter<'a> {
     }
 
     ///
-    /// It transform the Frame2 to byte array.
+    /// It transform the Frame1 to byte array.
     ///
-    pub fn frame2(&self,
-                  frame_header: &mut FrameHeaderV2,
+    pub fn frame1(&self, frame1: Frame1) -> Result<Vec<u8>> {
+        let mut writable = Cursor::new(vec![0u8; 0]);
+        frame1.write(&mut writable)?;
+
+        let mut buf = Vec::new();
+        writable.copy(&mut buf)?;
+
+        Ok(buf)
+    }
+
+    ///
+    /// It transform the Frame2 to byte array.
+    ///
+    pub fn frame2(&self,
+                  frame_header: &mut FrameHeaderV2,
                   frame_body: FrameBody)
                   -> Result<Vec<u8>> {
         let mut writable = Cursor::new(vec![0u8; 0]);
@@ -661,8 +1677,8 @@ impl<'a> MetadataWriter<'a> {
             match frame_body {
                 FrameBody::OBJECT(_) => {}
                 _ => {
-                    return Err(Error::new(ErrorKind::InvalidData,
-                                          "Encrypted frame must be FrameBody::OBJECT."));
+                    return Err(Id3Error::new(error::ErrorKind::InvalidHeader,
+                                             "Encrypted frame must be FrameBody::OBJECT."));
                 }
             };
         }
@@ -672,7 +1688,7 @@ impl<'a> MetadataWriter<'a> {
         frame_header.id = id.to_string();
         frame_header.size = bytes.len() as u32;
         frame_header.write(&mut writable, 2)?;
-        writable.write(&bytes)?;
+        writable.write_all(&bytes)?;
 
         let mut buf = Vec::new();
         writable.copy(&mut buf)?;
@@ -702,8 +1718,8 @@ impl<'a> MetadataWriter<'a> {
                     return Ok(buf);
                 }
                 _ => {
-                    return Err(Error::new(ErrorKind::InvalidData,
-                                          "Encrypted frame must be FrameBody::OBJECT."));
+                    return Err(Id3Error::new(error::ErrorKind::InvalidHeader,
+                                             "Encrypted frame must be FrameBody::OBJECT."));
                 }
             };
         }
@@ -713,6 +1729,8 @@ impl<'a> MetadataWriter<'a> {
         frame_header.id = id.to_string();
 
         if frame_header.has_flag(FrameHeaderFlag::Compression) {
+            frame_header.data_length = Some(bytes.len() as u32);
+
             let mut encoder = ZlibEncoder::new(Vec::new(), Compression::Default);
             let _ = encoder.write(&bytes);
             bytes = encoder.finish()?;
@@ -722,7 +1740,7 @@ impl<'a> MetadataWriter<'a> {
         }
 
         frame_header.write(&mut writable, 3)?;
-        writable.write(&bytes)?;
+        writable.write_all(&bytes)?;
 
         let mut buf = Vec::new();
         writable.copy(&mut buf)?;
@@ -752,8 +1770,8 @@ impl<'a> MetadataWriter<'a> {
                     return Ok(buf);
                 }
                 _ => {
-                    return Err(Error::new(ErrorKind::InvalidData,
-                                          "Encrypted frame must be FrameBody::OBJECT."));
+                    return Err(Id3Error::new(error::ErrorKind::InvalidHeader,
+                                             "Encrypted frame must be FrameBody::OBJECT."));
                 }
             }
         }
@@ -763,6 +1781,13 @@ impl<'a> MetadataWriter<'a> {
         frame_header.id = id.to_string();
         frame_header.size = bytes.len() as u32;
 
+        //
+        // The data length indicator is the size before it is unsynchronised and compressed.
+        //
+        if frame_header.has_flag(FrameHeaderFlag::DataLength) {
+            frame_header.data_length = Some(bytes.len() as u32);
+        }
+
         if frame_header.has_flag(FrameHeaderFlag::Unsynchronisation) {
             debug!("write {} unsynchronization", id);
 
@@ -781,7 +1806,7 @@ impl<'a> MetadataWriter<'a> {
         }
 
         frame_header.write(&mut writable, 4)?;
-        writable.write(&bytes)?;
+        writable.write_all(&bytes)?;
 
         let mut buf = Vec::new();
         writable.copy(&mut buf)?;
@@ -801,6 +1826,45 @@ impl<'a> MetadataWriter<'a> {
         Ok(buf)
     }
 
+    ///
+    /// It transform a lazy frame to byte array without decoding it.
+    /// the flags and the bytes of the body are written as they are read.
+    ///
+    pub fn lazy_frame(&self, frame: &LazyFrame) -> Result<Vec<u8>> {
+        let mut writable = Cursor::new(vec![]);
+        let size = frame.bytes.len() as u32;
+
+        match frame.header {
+            FrameHeader::V22(ref header) => {
+                writable.write_string(header.id.as_str())?;
+                writable.write_u24(size)?;
+            }
+            FrameHeader::V23(ref header) => {
+                writable.write_string(header.id.as_str())?;
+                writable.write_u32(size)?;
+                writable.write_u8(header.status_flag)?;
+                writable.write_u8(header.encoding_flag)?;
+            }
+            FrameHeader::V24(ref header) => {
+                writable.write_string(header.id.as_str())?;
+                if frame.plain_size {
+                    writable.write_u32(size)?;
+                } else {
+                    writable.write_synchsafe(size)?;
+                }
+                writable.write_u8(header.status_flag)?;
+                writable.write_u8(header.encoding_flag)?;
+            }
+        }
+
+        writable.write_all(&frame.bytes)?;
+
+        let mut buf = Vec::new();
+        writable.copy(&mut buf)?;
+
+        Ok(buf)
+    }
+
     ///
     /// It transform the Frame 2.x to byte array.
     ///
@@ -813,17 +1877,17 @@ impl<'a> MetadataWriter<'a> {
             FrameHeader::V22(ref mut frame_header) => {
                 let bytes = &self.frame2(frame_header, frame_body)?;
                 debug!("write frame2: {}, {}", frame_header.id, bytes.len());
-                writable.write(bytes)?;
+                writable.write_all(bytes)?;
             }
             FrameHeader::V23(ref mut frame_header) => {
                 let bytes = &self.frame3(frame_header, frame_body)?;
                 debug!("write frame3: {}, {}", frame_header.id, bytes.len());
-                writable.write(bytes)?;
+                writable.write_all(bytes)?;
             }
             FrameHeader::V24(ref mut frame_header) => {
                 let bytes = &self.frame4(frame_header, frame_body)?;
                 debug!("write frame4: {}, {}", frame_header.id, bytes.len());
-                writable.write(bytes)?;
+                writable.write_all(bytes)?;
             }
         }
 
@@ -836,20 +1900,46 @@ impl<'a> MetadataWriter<'a> {
     ///
     /// It transform all the units to byte array.
     /// if Unit::Header is not given, create new one as version 4.
+    /// if the footer flag of head is set, a footer is created.
+    ///
+    /// @return tuple. (frame1 exist, head size including the footer, bytes)
     ///
     pub fn to_bytes(&self, units: Vec<Unit>) -> Result<(bool, u32, Vec<u8>)> {
         let mut writable = Cursor::new(vec![]);
 
         let mut head_wrap = None;
+        let mut ext_head_wrap = None;
         let mut frame1_wrap = None;
-        let mut frames = Vec::new();
+        let mut frame_bytes = Vec::new();
+        let mut padding = Vec::new();
 
         for unit in units {
             match unit {
                 Unit::Header(head) => head_wrap = Some(head),
+                Unit::ExtendedHeader(ext_head) => ext_head_wrap = Some(ext_head),
                 Unit::FrameV1(frame) => frame1_wrap = Some(frame),
-                Unit::FrameV2(frame_header, frame_body) => frames.push((frame_header, frame_body)),
-                _ => (),
+                Unit::FrameV2(frame_header, frame_body) => {
+                    frame_bytes.append(&mut self.frame((frame_header, frame_body))?);
+                }
+                Unit::LazyFrameV2(ref frame) if self.round_trip => {
+                    frame_bytes.append(&mut self.lazy_frame(frame)?);
+                }
+                Unit::LazyFrameV2(frame) => {
+                    let frame_body = frame.decode()?;
+                    frame_bytes.append(&mut self.frame((frame.header, frame_body))?);
+                }
+                //
+                // Padding is written only in round-trip mode. Footer is created from the head.
+                //
+                Unit::Padding { size, all_zero, mut bytes, .. } => {
+                    if self.round_trip {
+                        if all_zero {
+                            bytes.resize(size, 0);
+                        }
+                        padding.append(&mut bytes);
+                    }
+                }
+                Unit::Footer(_) => (),
             }
         }
 
@@ -866,7 +1956,35 @@ impl<'a> MetadataWriter<'a> {
             _ => head_wrap.unwrap(),
         };
 
-        let mut frame_bytes = self.frames(frames)?;
+        //
+        // A tag that has a footer must not have padding. (2.4 §3.4)
+        //
+        if head.has_flag(HeadFlag::FooterPresent) && !padding.is_empty() {
+            debug!("padding is dropped with the footer");
+            padding.clear();
+        }
+
+        //
+        // Extended header is written when the flag of head is set.
+        //
+        if head.has_flag(HeadFlag::ExtendedHeader) {
+            match ext_head_wrap {
+                Some(ext_head) => {
+                    let mut bytes = self.ext_head_with_padding(head.version,
+                                                               ext_head,
+                                                               &frame_bytes,
+                                                               &padding)?;
+                    bytes.append(&mut frame_bytes);
+                    frame_bytes = bytes;
+                }
+                None => {
+                    debug!("no extended header");
+                    head.flag = head.flag & !BIT6;
+                }
+            }
+        }
+
+        frame_bytes.append(&mut padding);
 
         //
         // Head level Unsynchronisation.
@@ -880,15 +1998,28 @@ impl<'a> MetadataWriter<'a> {
 
         head.size = frame_bytes.len() as u32;
 
-        let head_size = head.size;
+        let mut head_size = head.size;
+
+        let footer = if head.has_flag(HeadFlag::FooterPresent) {
+            let mut footer = head.clone();
+            footer.tag_id = "3DI".to_string();
+            head_size = head_size + 10;
+            Some(footer)
+        } else {
+            None
+        };
+
+        writable.write_all(&self.head(head)?)?;
+        writable.write_all(&frame_bytes)?;
 
-        writable.write(&self.head(head)?)?;
-        writable.write(&frame_bytes)?;
+        if let Some(footer) = footer {
+            writable.write_all(&self.head(footer)?)?;
+        }
 
         let has_frame1 = match frame1_wrap {
             None => false,
             Some(frame1) => {
-                writable.write(&self.frame1(frame1)?)?;
+                writable.write_all(&self.frame1(frame1)?)?;
                 true
             }
         };
@@ -900,70 +2031,1136 @@ impl<'a> MetadataWriter<'a> {
     }
 }
 
-///
-/// MetadataReader implement a Iterator.
-///
-/// because instead of loading all the metadata information,
-/// it support to read Unit step by step and there is convenient methods
-/// in Iterator like filter, map.
-///
-impl Iterator for MetadataReader {
-    type Item = Unit;
+impl<R> MetadataReader<R>
+    where R: Read + Seek
+{
+    ///
+    /// It return a Iterator that yield the error too.
+    ///
+    /// The Iterator of MetadataReader skip a broken unit and continue to next tag or the frame1.
+    /// but `try_iter` yield `Err` for it, so the end of the units can be distinguished from
+    /// a failure. After `Err`, it continue with the same way.
+    ///
+    pub fn try_iter<'a>(&'a mut self) -> TryIter<'a, R> {
+        TryIter { reader: self }
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
+    ///
+    /// It push the units to a `TagVisitor` until the end of the units.
+    ///
+    /// The frames are read here instead of the Iterator. `TagVisitor::on_frame_header` is
+    /// given the header and the bytes of the body that are borrowed, and the body is decoded
+    /// only if it return `FrameAction::Decode`. a broken unit is passed to
+    /// `TagVisitor::on_error` and it continue with the same way of the Iterator.
+    ///
+    pub fn visit<V>(&mut self, visitor: &mut V)
+        where V: TagVisitor
+    {
+        loop {
+            //
+            // The frame bytes are read here to call `on_frame_header` for the first frame too.
+            //
+            if let Status::Head(head) = self.next.clone() {
+                if let Err(e) = self.set_frame_bytes_next(&head) {
+                    self.diagnostics.push(Diagnostic::new(Severity::Error, &e));
+                    visitor.on_error(&e);
+                }
+                continue;
+            }
 
-        fn do_next_frame1(reader: &mut MetadataReader) -> Option<Unit> {
-            match reader.file.metadata() {
-                Err(_) => None,
-                Ok(metadata) => {
-                    match reader.file.frame1(metadata.len() as usize) {
-                        Err(_) => None,
-                        Ok(frame1) => {
-                            reader.next = Status::Error;
-                            Some(frame1)
+            if let Status::Frame(head) = self.next.clone() {
+                if let Err(e) = self.visit_frame(&head, visitor) {
+                    self.diagnostics.push(Diagnostic::new(Severity::Error, &e));
+                    visitor.on_error(&e);
+                }
+                continue;
+            }
+
+            let unit = match self.next_unit() {
+                None => break,
+                Some(Err(e)) => {
+                    visitor.on_error(&e);
+                    continue;
+                }
+                Some(Ok(unit)) => unit,
+            };
+
+            match unit {
+                Unit::Header(head) => visitor.on_header(&head),
+                Unit::ExtendedHeader(ext_head) => visitor.on_extended_header(&ext_head),
+                Unit::FrameV2(header, body) => visitor.on_frame_body(&header, &body),
+                Unit::LazyFrameV2(frame) => {
+                    match frame.decode_with(&self.options) {
+                        Ok(body) => visitor.on_frame_body(&frame.header, &body),
+                        Err(e) => {
+                            let e = e.with_frame_id(frame.header.id());
+                            visitor.on_error(&e);
                         }
                     }
                 }
+                Unit::Padding { offset, size, all_zero, .. } => {
+                    visitor.on_padding(offset, size, all_zero)
+                }
+                Unit::Footer(footer) => visitor.on_footer(&footer),
+                Unit::FrameV1(frame1) => visitor.on_v1(&frame1),
+            }
+        }
+    }
+
+    //
+    // It read a frame for `visit`. the body is borrowed from the frame bytes until the visitor
+    // decide to decode it. the frames that are not allowed by `with_frame_ids` are skipped
+    // without asking the visitor, but SEEK frame is read to find the next tag.
+    //
+    fn visit_frame<V>(&mut self, head: &Head, visitor: &mut V) -> result::Result<(), Id3Error>
+        where V: TagVisitor
+    {
+        let frame_offset = self.offset + 10 + Cursor::position(&self.frame_readable) as usize;
+
+        self.count_frame(head, frame_offset)?;
+
+        let mut header = match read_frame_header(&mut self.frame_readable, head.version) {
+            Ok(header) => header,
+            Err(e) => {
+                self.frame_error_next(head, frame_offset);
+                return Err(e.with_offset(frame_offset));
+            }
+        };
+
+        let plain_size = self.check_synchsafe(head, frame_offset, header.id_str());
+
+        let size = header.size() as usize;
+        if let Err(e) = check_limit("Frame size", size, self.options.limits.max_frame_size) {
+            self.frame_error_next(head, frame_offset);
+            return Err(e.with_offset(frame_offset).with_frame_id(header.id()));
+        }
+
+        let start = Cursor::position(&self.frame_readable) as usize;
+        let end = start + size;
+        self.frame_readable.set_position(end as u64);
+
+        let action = {
+            let bytes = &self.frame_readable.get_ref()[start..end];
+            header.read_flag_bytes(bytes);
+
+            if self.is_frame_allowed(head, header.id_str()) {
+                visitor.on_frame_header(&header, bytes)
+            } else {
+                FrameAction::Skip
+            }
+        };
+
+        if action == FrameAction::Skip && header.id_str() != id::SEEK {
+            self.set_frame_next(head);
+            return Ok(());
+        }
+
+        let frame = LazyFrame {
+            header: header,
+            bytes: self.frame_readable.get_ref()[start..end].to_vec(),
+            limits: self.options.limits.clone(),
+            plain_size: plain_size,
+        };
+
+        let mut violations = Violations::new(self.options.strict);
+        let body = match frame.decode_with_violations(&self.options, &mut violations) {
+            Ok(body) => body,
+            Err(e) => {
+                self.frame_error_next(head, frame_offset);
+                return Err(e
+                    .with_offset(frame_offset)
+                    .with_frame_id(frame.header.id()));
+            }
+        };
+
+        if let FrameBody::SEEK(ref seek) = body {
+            self.seek_next_tag(head, seek);
+        }
+        self.set_frame_next(head);
+
+        if action == FrameAction::Skip {
+            return Ok(());
+        }
+
+        let header = frame.header;
+        let body = self.decrypt_body(&header, body, &mut violations)
+            .map_err(|e| e.with_offset(frame_offset).with_frame_id(header.id()));
+
+        for warning in violations.warnings {
+            let warning = warning.with_offset(frame_offset).with_frame_id(header.id());
+            self.diagnostics.push(Diagnostic::new(Severity::Warning, &warning));
+        }
+
+        visitor.on_frame_body(&header, &body?);
+
+        Ok(())
+    }
+
+    //
+    // It read the ENCR frames of the tag from the first frame. the position is not changed.
+    //
+    fn read_encryptions(&mut self, head: &Head) {
+        self.encryptions.clear();
+
+        if self.decryptors.is_empty() {
+            return;
+        }
+
+        let position = self.frame_readable.position();
+
+        while let Ok(frame) = LazyFrame::read_with(&mut self.frame_readable,
+                                                   head.version,
+                                                   &self.options) {
+            let id = frame.header.id();
+            if !id.starts_with(|c: char| c.is_ascii_uppercase()) {
+                break;
+            }
+
+            if id == id::ENCR {
+                if let Ok(FrameBody::ENCR(encr)) = frame.decode_with(&self.options) {
+                    self.encryptions.push(encr);
+                }
+            }
+        }
+
+        self.frame_readable.set_position(position);
+    }
+
+    //
+    // If the body is encrypted and a decryptor is registered for its method, it is decrypted
+    // and read. if not, it is returned as it is.
+    //
+    fn decrypt_body(&self,
+                    header: &FrameHeader,
+                    body: FrameBody,
+                    violations: &mut Violations)
+                    -> Result<FrameBody> {
+        let (id, bytes) = match body {
+            FrameBody::SKIP(id, bytes) => (id, bytes),
+            body => return Ok(body),
+        };
+
+        let method = header.encryption_method();
+        let encr = self.encryptions.iter().find(|encr| Some(encr.method_symbol) == method);
+
+        let decryptor = encr.and_then(|encr| {
+            self.decryptors
+                .iter()
+                .find(|&&(ref key, _)| match *key {
+                    DecryptorKey::Owner(ref owner) => *owner == encr.owner_identifier,
+                    DecryptorKey::Method(symbol) => symbol == encr.method_symbol,
+                })
+                .map(|&(_, ref decryptor)| decryptor)
+        });
+
+        let (encr, decryptor) = match (encr, decryptor) {
+            (Some(encr), Some(decryptor)) => (encr, decryptor),
+            _ => return Ok(FrameBody::SKIP(id, bytes)),
+        };
+
+        debug!("'{}' is decrypted with the method {}", id, encr.method_symbol);
+
+        let content = decryptor.decrypt(encr, header, &bytes)?;
+        let content = decompressed_content(header,
+                                           Cow::Owned(content),
+                                           header.data_length(),
+                                           &self.options)?;
+
+        let version = match *header {
+            FrameHeader::V22(_) => 2,
+            FrameHeader::V23(_) => 3,
+            FrameHeader::V24(_) => 4,
+        };
+
+        read_framebody(&id, version, Cursor::new(content.into_owned()), violations)
+    }
+
+    //
+    // The position and the id of the frame that will be read.
+    // if the tag is unsynchronised, the position is the one of the synchronised bytes.
+    //
+    fn frame_context(&mut self, head: &Head) -> (usize, String) {
+        let readable = &mut self.frame_readable;
+
+        let position = Cursor::position(readable) as usize;
+        let id_len = if head.version == 2 { 3 } else { 4 };
+        let id = readable.look_string(id_len).unwrap_or(String::new());
+
+        (self.offset + 10 + position, id)
+    }
+
+    //
+    // If the frame is in the allow-list of `with_frame_ids`.
+    //
+    fn is_frame_allowed(&self, head: &Head, frame_id: &str) -> bool {
+        match self.frame_ids {
+            None => true,
+            Some(ref ids) => {
+                ids.iter().any(|id| id == frame_id) ||
+                (head.version == 2 && ids.iter().any(|id| *id == frame2_to_frame4(frame_id)))
+            }
+        }
+    }
+
+    //
+    // If the frame is SEEK, the next tag is the one that it point to.
+    //
+    fn follow_seek(&mut self, head: &Head, frame: &Unit) {
+        let seek = match frame {
+            &Unit::FrameV2(_, FrameBody::SEEK(ref seek)) => Some(seek.clone()),
+            &Unit::LazyFrameV2(ref frame) if frame.header.id() == id::SEEK => {
+                match frame.decode_with(&self.options) {
+                    Ok(FrameBody::SEEK(seek)) => Some(seek),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
+        if let Some(seek) = seek {
+            self.seek_next_tag(head, &seek);
+        }
+    }
+
+    //
+    // The offset of SEEK frame is calculated from the end of this tag.
+    //
+    fn seek_next_tag(&mut self, head: &Head, seek: &SEEK) {
+        let mut tag_end = self.offset + 10 + head.size as usize;
+        if head.has_flag(HeadFlag::FooterPresent) {
+            tag_end = tag_end + 10;
+        }
+        self.seek = Some(tag_end + seek.offset_to_next_tag as usize);
+    }
+
+    //
+    // After a frame that can not be read, the rest of the tag is ignored.
+    // but with `ParseOptions::resync`, it find a next frame and continue from there.
+    // if there is no frame, the rest is read as the padding.
+    //
+    fn frame_error_next(&mut self, head: &Head, frame_offset: usize) {
+        if !self.options.resync {
+            self.next = Status::NextTag;
+            return;
+        }
+
+        let start = frame_offset - self.offset - 10;
+        let found = find_frame(self.frame_readable.get_ref(), start + 1, head.version);
+
+        match found {
+            Some(position) => {
+                let warning = Id3Error::new(error::ErrorKind::SkippedBytes,
+                                            format!("{} bytes are skipped", position - start))
+                    .with_offset(frame_offset);
+                self.diagnostics.push(Diagnostic::new(Severity::Warning, &warning));
+
+                self.frame_readable.set_position(position as u64);
+                self.next = Status::Frame(head.clone());
+            }
+            None => {
+                self.frame_readable.set_position(start as u64);
+                self.next = Status::Padding(head.clone());
+            }
+        }
+    }
+
+    //
+    // The frames that are not in the allow-list are skipped. but SEEK frame is read to find
+    // the next tag.
+    //
+    fn skip_frames(&mut self) -> result::Result<(), Id3Error> {
+        while let Status::Frame(head) = self.next.clone() {
+            let (frame_offset, frame_id) = self.frame_context(&head);
+
+            if self.is_frame_allowed(&head, &frame_id) {
+                break;
+            }
+
+            self.skip_frame_next(&head, frame_offset, frame_id)?;
+        }
+
+        Ok(())
+    }
+
+    //
+    // The number of the frames must not be larger than `Limits::max_frames`.
+    // if it is, the rest of the tag is ignored.
+    //
+    fn count_frame(&mut self, head: &Head, frame_offset: usize) -> result::Result<(), Id3Error> {
+        let max_frames = self.options.limits.max_frames;
+
+        if let Err(e) = check_limit("Frame count", self.frame_count + 1, max_frames) {
+            self.set_tag_end_next(head);
+            return Err(e.with_offset(frame_offset));
+        }
+
+        self.frame_count = self.frame_count + 1;
+
+        Ok(())
+    }
+
+    //
+    // In 2.4, a frame size that is not synchsafe is read as a plain size and kept as a warning.
+    // @return true if the size is plain.
+    //
+    fn check_synchsafe(&mut self, head: &Head, frame_offset: usize, frame_id: &str) -> bool {
+        if head.version != 4 {
+            return false;
+        }
+
+        let position = frame_offset - self.offset - 10;
+
+        if let Some(size) = plain_frame_size(self.frame_readable.get_ref(), position) {
+            let warning = Id3Error::new(error::ErrorKind::NotSynchsafe,
+                                        format!("Frame size {} is not synchsafe", size))
+                .with_offset(frame_offset)
+                .with_frame_id(frame_id);
+            self.diagnostics.push(Diagnostic::new(Severity::Warning, &warning));
+            return true;
+        }
+
+        false
+    }
+
+    //
+    // It skip a frame and set the next status. but SEEK frame is read to find the next tag.
+    //
+    fn skip_frame_next(&mut self,
+                       head: &Head,
+                       frame_offset: usize,
+                       frame_id: String)
+                       -> result::Result<(), Id3Error> {
+        self.count_frame(head, frame_offset)?;
+        self.check_synchsafe(head, frame_offset, &frame_id);
+
+        let result = if frame_id == id::SEEK {
+            let mut violations = Violations::new(self.options.strict);
+            self.readable
+                .frame_with(head, &mut self.frame_readable, &self.options, &mut violations)
+                .map(|frame| self.follow_seek(head, &frame))
+        } else {
+            self.readable.skip_frame(head, &mut self.frame_readable)
+        };
+
+        if let Err(e) = result {
+            self.frame_error_next(head, frame_offset);
+            return Err(e.with_offset(frame_offset).with_frame_id(frame_id));
+        }
+
+        self.set_frame_next(head);
+
+        Ok(())
+    }
+
+    //
+    // It read the frame bytes of a tag. the next is the extended header or the first frame.
+    //
+    fn set_frame_bytes_next(&mut self, head: &Head) -> result::Result<(), Id3Error> {
+        match self.readable.frame_bytes_with(head, &self.options) {
+            Err(e) => {
+                self.next = Status::NextTag;
+                Err(e.with_offset(self.offset + 10))
+            }
+            Ok(readable) => {
+                self.frame_readable = readable;
+                self.frame_count = 0;
+                if head.has_flag(HeadFlag::ExtendedHeader) {
+                    self.next = Status::ExtendedHeader(head.clone());
+                } else {
+                    self.read_encryptions(head);
+                    self.set_frame_next(head);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    //
+    // It read a next unit and keep the error as a diagnostic.
+    //
+    fn next_unit(&mut self) -> Option<result::Result<Unit, Id3Error>> {
+        let unit = self.read_unit();
+
+        if let Some(Err(ref e)) = unit {
+            self.diagnostics.push(Diagnostic::new(Severity::Error, e));
+        }
+
+        unit
+    }
+
+    //
+    // It read a next unit. None is the end of the units.
+    //
+    fn read_unit(&mut self) -> Option<result::Result<Unit, Id3Error>> {
+
+        fn do_next_frame1<R>(reader: &mut MetadataReader<R>)
+                             -> Option<result::Result<Unit, Id3Error>>
+            where R: Read + Seek
+        {
+            reader.next = Status::Error;
+
+            //
+            // The frame1 is optional. so it is not a error if it does not exist.
+            //
+            match reader.readable.position_end(0) {
+                Err(_) => None,
+                Ok(len) => reader.readable.frame1(len).ok().map(Ok),
+            }
+        }
+
+        fn do_head<R>(reader: &mut MetadataReader<R>,
+                      offset: usize)
+                      -> Option<result::Result<Unit, Id3Error>>
+            where R: Read + Seek
+        {
+            reader.offset = offset;
+            reader.tags.push(offset);
+
+            match reader.readable.position(offset).and_then(|_| reader.readable.head()) {
+                //
+                // The first tag is optional. but the tag pointed by SEEK frame or a footer
+                // must exist.
+                //
+                Err(_) if reader.tags.len() == 1 => do_next_tag(reader),
+                Err(e) => {
+                    reader.next = Status::NextTag;
+                    Some(Err(e.with_offset(offset)))
+                }
+                Ok(Unit::Header(head)) => {
+                    let mut tag_end = offset + 10 + head.size as usize;
+                    if head.has_flag(HeadFlag::FooterPresent) {
+                        tag_end = tag_end + 10;
+                    }
+                    reader.tag_end = Some(tag_end);
+                    reader.tag_offsets.push(offset);
+
+                    reader.next = Status::Head(head.clone());
+                    Some(Ok(Unit::Header(head)))
+                }
+                Ok(unit) => Some(Ok(unit)),
             }
         }
 
+        //
+        // After a tag, it read the next tag if exist. if not, it read the frame1.
+        //
+        fn do_next_tag<R>(reader: &mut MetadataReader<R>)
+                          -> Option<result::Result<Unit, Id3Error>>
+            where R: Read + Seek
+        {
+            match reader.next_tag_offset() {
+                Some(offset) => do_head(reader, offset),
+                None => do_next_frame1(reader),
+            }
+        }
+
+        if let Err(e) = self.skip_frames() {
+            return Some(Err(e));
+        }
+
         let next = self.next.clone();
 
         match next {
             Status::None => {
-                match self.file.head() {
-                    Err(_) => do_next_frame1(self),
-                    Ok(header) => {
-                        self.set_head_next(&header);
-                        Some(header)
+                match self.readable.skip_bytes(0) {
+                    Err(_) => None,
+                    Ok(start) => {
+                        //
+                        // The first tag can be after junk bytes in the search window.
+                        //
+                        let window = self.options.search_window;
+                        let offset = match self.readable.find_head(start, window) {
+                            Ok(Some(offset)) => offset,
+                            _ => start,
+                        };
+
+                        if offset > start {
+                            let warning =
+                                Id3Error::new(error::ErrorKind::SkippedBytes,
+                                              format!("{} bytes are skipped", offset - start))
+                                    .with_offset(start);
+                            self.diagnostics.push(Diagnostic::new(Severity::Warning, &warning));
+                        }
+
+                        do_head(self, offset)
                     }
                 }
             }
 
+            Status::Head(ref head) => {
+                match self.set_frame_bytes_next(head) {
+                    Err(e) => Some(Err(e)),
+                    Ok(()) => self.read_unit(),
+                }
+            }
+
             Status::ExtendedHeader(ref head) => {
-                match self.file.ext_head(head) {
-                    Err(_) => None,
+                match self.readable.ext_head(head, &mut self.frame_readable) {
+                    //
+                    // The flag of extended header is set but there is no extended header.
+                    // it read frames from first of the frame bytes.
+                    //
+                    Err(e) => {
+                        warn!("Invalid extended header: {}", e);
+                        self.frame_readable.set_position(0);
+                        self.read_encryptions(head);
+                        self.set_frame_next(head);
+                        Some(Err(e.with_offset(self.offset + 10)))
+                    }
                     Ok(ext_head) => {
-                        self.set_ext_head_next(head);
-                        Some(ext_head)
+                        self.read_encryptions(head);
+                        self.set_frame_next(head);
+                        Some(Ok(ext_head))
                     }
                 }
             }
 
-            Status::Frame(ref head, ref readable) => {
-                match self.file.frame(head, readable.clone()) {
-                    Err(_) => do_next_frame1(self),
+            Status::Frame(ref head) => {
+                let (frame_offset, frame_id) = self.frame_context(head);
+
+                if let Err(e) = self.count_frame(head, frame_offset) {
+                    return Some(Err(e));
+                }
+                self.check_synchsafe(head, frame_offset, &frame_id);
+
+                //
+                // In lenient mode, the problems that are recovered while reading a frame are
+                // kept as warnings.
+                //
+                let mut violations = Violations::new(self.options.strict);
+
+                let result = if self.options.lazy {
+                    self.readable.lazy_frame_with(head, &mut self.frame_readable, &self.options)
+                } else {
+                    self.readable.frame_with(head,
+                                             &mut self.frame_readable,
+                                             &self.options,
+                                             &mut violations)
+                };
+
+                match result {
+                    Err(e) => {
+                        self.frame_error_next(head, frame_offset);
+                        let e = e.with_offset(frame_offset).with_frame_id(frame_id);
+                        Some(Err(e))
+                    }
                     Ok(frame) => {
-                        self.set_frame_next(head, readable.clone());
-                        Some(frame)
+                        self.follow_seek(head, &frame);
+                        self.set_frame_next(head);
+
+                        let frame = match frame {
+                            Unit::FrameV2(header, body) => {
+                                self.decrypt_body(&header, body, &mut violations)
+                                    .map(|body| Unit::FrameV2(header, body))
+                            }
+                            frame => Ok(frame),
+                        };
+
+                        for warning in violations.warnings {
+                            let warning = warning.with_offset(frame_offset)
+                                .with_frame_id(frame_id.as_str());
+                            self.diagnostics.push(Diagnostic::new(Severity::Warning, &warning));
+                        }
+
+                        Some(frame.map_err(|e| {
+                            e.with_offset(frame_offset).with_frame_id(frame_id)
+                        }))
+                    }
+                }
+            }
+
+            Status::Padding(ref head) => {
+                let tag_end = self.offset + 10 + head.size as usize;
+
+                match self.readable.padding(tag_end, &mut self.frame_readable) {
+                    Err(e) => {
+                        self.next = Status::NextTag;
+                        Some(Err(e.with_offset(tag_end)))
+                    }
+                    Ok(Unit::Padding { offset, all_zero: false, .. }) if self.options.strict => {
+                        self.set_tag_end_next(head);
+                        Some(Err(Id3Error::new(error::ErrorKind::InvalidPadding,
+                                               "Padding is not zero")
+                            .with_offset(offset)))
+                    }
+                    Ok(padding) => {
+                        if let Unit::Padding { offset, all_zero: false, .. } = padding {
+                            let warning = Id3Error::new(error::ErrorKind::InvalidPadding,
+                                                        "Padding is not zero")
+                                .with_offset(offset);
+                            self.diagnostics.push(Diagnostic::new(Severity::Warning, &warning));
+                        }
+
+                        self.set_tag_end_next(head);
+                        Some(Ok(padding))
                     }
                 }
             }
 
-            Status::Frame1 => do_next_frame1(self),
+            Status::Footer(ref head) => {
+                let footer_offset = self.offset + 10 + head.size as usize;
+
+                self.next = Status::NextTag;
+                Some(self.readable
+                    .footer(head)
+                    .map_err(|e| e.with_offset(footer_offset)))
+            }
+
+            Status::NextTag => do_next_tag(self),
 
             Status::Error => None,
         }
+    }
+}
+
+///
+/// MetadataReader implement a Iterator.
+///
+/// because instead of loading all the metadata information,
+/// it support to read Unit step by step and there is convenient methods
+/// in Iterator like filter, map.
+///
+/// A broken unit is skipped. use `try_iter` to get the errors.
+///
+impl<R> Iterator for MetadataReader<R>
+    where R: Read + Seek
+{
+    type Item = Unit;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.next_unit() {
+                None => return None,
+                Some(Ok(unit)) => return Some(unit),
+                Some(Err(e)) => debug!("Skip a unit: {}", e),
+            }
+        }
+    }
+}
+
+///
+/// A Iterator that yield `Result<Unit, Id3Error>`. it is created by `MetadataReader::try_iter`.
+///
+pub struct TryIter<'a, R: 'a> {
+    reader: &'a mut MetadataReader<R>,
+}
+
+impl<'a, R> Iterator for TryIter<'a, R>
+    where R: Read + Seek
+{
+    type Item = result::Result<Unit, Id3Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.reader.next_unit()
+    }
+}
+
+///
+/// The return of `TagVisitor::on_frame_header`.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameAction {
+    // The body is not read.
+    Skip,
+    // The body is decoded and passed to `on_frame_body`.
+    Decode,
+}
+
+///
+/// Push-style parsing with `MetadataReader::visit`.
+///
+/// Every method has a default that do nothing, so only the needed ones are implemented.
+/// the frames are read with the same way of `ReadOp::frame2`, `frame3` and `frame4`.
+///
+pub trait TagVisitor {
+    fn on_header(&mut self, _head: &Head) {}
+
+    fn on_extended_header(&mut self, _ext_head: &ExtendedHeader) {}
+
+    ///
+    /// It decide whether the body of the frame is decoded. default is `FrameAction::Decode`.
+    /// 'bytes' is the body as it is in the tag, same with `LazyFrame::bytes`. it is borrowed
+    /// from the frame bytes of the tag.
+    ///
+    fn on_frame_header(&mut self, _header: &FrameHeader, _bytes: &[u8]) -> FrameAction {
+        FrameAction::Decode
+    }
+
+    fn on_frame_body(&mut self, _header: &FrameHeader, _body: &FrameBody) {}
+
+    fn on_padding(&mut self, _offset: usize, _size: usize, _all_zero: bool) {}
+
+    fn on_footer(&mut self, _footer: &Head) {}
+
+    fn on_v1(&mut self, _frame1: &Frame1) {}
+
+    fn on_error(&mut self, _err: &Id3Error) {}
+}
+
+///
+/// The key that a `FrameDecryptor` is registered for. it is matched with a ENCR frame.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecryptorKey {
+    // The owner identifier of the ENCR frame. e.g) a URL of the owner.
+    Owner(String),
+    // The method symbol of the ENCR frame.
+    Method(u8),
+}
+
+///
+/// It decrypt a encrypted frame. it is registered with `MetadataReader::with_decryptor`.
+///
+pub trait FrameDecryptor: Send {
+    ///
+    /// @encr: the ENCR frame of the method in the same tag.
+    /// @header: the header of the encrypted frame.
+    /// @bytes: the encrypted bytes. the extra bytes of the flags are removed and they are
+    ///         synchronised.
+    /// @return the decrypted bytes. they are decompressed after if the frame is compressed.
+    ///
+    fn decrypt(&self, encr: &ENCR, header: &FrameHeader, bytes: &[u8]) -> Result<Vec<u8>>;
+}
+
+//
+// It does not compile if the reader or the units are not `Send`.
+//
+#[allow(dead_code)]
+fn assert_send() {
+    fn is_send<T: Send>() {}
+
+    is_send::<MetadataReader<File>>();
+    is_send::<MetadataReader<Cursor<Vec<u8>>>>();
+    is_send::<MetadataReader<Cursor<&[u8]>>>();
+    is_send::<Unit>();
+    is_send::<LazyFrame>();
+    is_send::<Diagnostic>();
+    is_send::<Id3Error>();
+}
+
+//
+// If the bytes look like a header. the version is 2 ~ 4 and the size is synchsafe.
+//
+fn is_head(bytes: &[u8]) -> bool {
+    bytes.len() >= 10 && &bytes[0..3] == b"ID3" && bytes[3] >= 2 && bytes[3] <= 4 &&
+    bytes[4] != 0xff && bytes[6..10].iter().all(|b| *b < 0x80)
+}
+
+//
+// If the bytes are a frame id. e.g) 'TIT2' or 'TT2'.
+//
+pub(crate) fn is_frame_id(id: &[u8]) -> bool {
+    id.len() > 0 && (id[0] as char).is_ascii_uppercase() &&
+    id[1..].iter().all(|b| (*b as char).is_ascii_uppercase() || (*b as char).is_ascii_digit())
+}
+
+//
+// Some encoders wrote the frame sizes of 2.4 as plain 32-bit integers. if the synchsafe size
+// of the frame at 'position' does not end at the next frame or the padding, but the plain size
+// does, it return the plain size.
+//
+pub(crate) fn plain_frame_size(bytes: &[u8], position: usize) -> Option<u32> {
+    if position + 10 > bytes.len() {
+        return None;
+    }
+
+    let synchsafe_size = |position: usize| {
+        bytes[position + 4..position + 8]
+            .iter()
+            .fold(0usize, |size, b| (size << 7) | (*b & 0x7f) as usize)
+    };
+
+    let plain = bytes[position + 4..position + 8]
+        .iter()
+        .fold(0usize, |size, b| (size << 8) | *b as usize);
+    let synchsafe = synchsafe_size(position);
+
+    //
+    // The end of a frame is the end of the bytes, the padding or the next frame.
+    // the next frame must have a valid id and fit in the bytes.
+    //
+    let is_frame_end = |end: usize| {
+        end == bytes.len() ||
+        (end < bytes.len() && bytes[end..].iter().all(|b| *b == 0)) ||
+        (end + 10 <= bytes.len() && is_frame_id(&bytes[end..end + 4]) &&
+         end + 10 + synchsafe_size(end) <= bytes.len())
+    };
+
+    let body = position + 10;
+    if plain == synchsafe || is_frame_end(body + synchsafe) || !is_frame_end(body + plain) {
+        return None;
+    }
+
+    Some(plain as u32)
+}
+
+//
+// It find a position from 'start' that looks like a frame header.
+// the id is valid and the size is in the remain bytes.
+//
+fn find_frame(bytes: &Vec<u8>, start: usize, version: u8) -> Option<usize> {
+    let (id_len, size_len) = if version == 2 { (3, 3) } else { (4, 4) };
+    let header_len = if version == 2 { 6 } else { 10 };
+
+    let mut position = start;
+    while position + header_len <= bytes.len() {
+        let id = &bytes[position..position + id_len];
+        let size_bytes = &bytes[position + id_len..position + id_len + size_len];
+
+        let valid_id = is_frame_id(id);
+
+        let size = match version {
+            2 | 3 => Some(size_bytes.iter().fold(0usize, |size, b| (size << 8) | *b as usize)),
+            _ if size_bytes.iter().all(|b| *b < 0x80) => {
+                Some(size_bytes.iter().fold(0usize, |size, b| (size << 7) | *b as usize))
+            }
+            _ => None,
+        };
+
+        if let Some(size) = size {
+            if valid_id && size > 0 && position + header_len + size <= bytes.len() {
+                return Some(position);
+            }
+        }
+
+        position = position + 1;
+    }
+
+    None
+}
+
+//
+// It return the content of a frame body. the extra bytes of the flags are removed, and it is
+// synchronised and decompressed if the flags are set. it is copied only in that case.
+//
+pub(crate) fn frame_content<'a>(header: &FrameHeader,
+                                bytes: &'a [u8],
+                                options: &ParseOptions)
+                                -> Result<Cow<'a, [u8]>> {
+    let id = header.id();
+    let version = match *header {
+        FrameHeader::V22(_) => 2,
+        FrameHeader::V23(_) => 3,
+        FrameHeader::V24(_) => 4,
+    };
+
+    let mut start = 0;
+
+    //
+    // If the flag of group-identity is set, one byte follow the frame size.
+    //
+    if header.has_flag(FrameHeaderFlag::GroupIdentity) {
+        start = start + 1;
+    }
+
+    //
+    // If the flag of encyrption is set, one byte follow the frame size.
+    //
+    if header.has_flag(FrameHeaderFlag::Encryption) {
+        start = start + 1;
+    }
+
+    //
+    // In 2.3, if the flag of compression is set, four byte follow the frame size.
+    // In 2.4, if the flag of data-length is set, four byte follow the frame size.
+    //
+    if (version == 3 && header.has_flag(FrameHeaderFlag::Compression)) ||
+       (version == 4 && header.has_flag(FrameHeaderFlag::DataLength)) {
+        start = start + 4;
+    }
+
+    if start > bytes.len() {
+        let err_msg = "Frame is shorter than the bytes of the flags";
+        warn!("{}: {}", id, err_msg);
+        return Err(Id3Error::new(error::ErrorKind::FrameTooLarge, err_msg)
+            .with_frame_id(id));
+    }
+
+    let data_length = frame_data_length(header, bytes);
+
+    //
+    // The declared size of a compressed frame is checked before it is decompressed.
+    //
+    if let (Some(size), true) = (data_length, header.has_flag(FrameHeaderFlag::Compression)) {
+        check_limit("Decompressed size",
+                    size as usize,
+                    options.limits.max_decompressed_size)
+            .map_err(|e| e.with_frame_id(id.as_str()))?;
+    }
+
+    let mut body_bytes = Cow::Borrowed(&bytes[start..]);
+
+    //
+    // If frame is unsynchronized, it re-build to synchronized byte.
+    //
+    if version == 4 && header.has_flag(FrameHeaderFlag::Unsynchronisation) {
+        debug!("'{}' is unsynchronised", id);
+        let len = body_bytes.len();
+        body_bytes = Cow::Owned(Cursor::new(&body_bytes[..]).to_synchronize(len)?);
+    }
+
+    //
+    // A encrypted body is decompressed after it is decrypted.
+    //
+    if header.has_flag(FrameHeaderFlag::Encryption) {
+        return Ok(body_bytes);
+    }
+
+    decompressed_content(header, body_bytes, data_length, options)
+}
+
+//
+// It decompress the content if the flag is set. the declared size must be same with the size
+// of the body.
+//
+fn decompressed_content<'a>(header: &FrameHeader,
+                            content: Cow<'a, [u8]>,
+                            data_length: Option<u32>,
+                            options: &ParseOptions)
+                            -> Result<Cow<'a, [u8]>> {
+    let id = header.id();
+    let mut body_bytes = content;
+
+    if header.has_flag(FrameHeaderFlag::Compression) {
+        debug!("'{}' is compressed", id);
+
+        //
+        // The compression frame is compressed using zlip.
+        //
+        let max = options.limits.max_decompressed_size;
+        body_bytes = Cow::Owned(decompress(&id, &body_bytes.into_owned(), max)?);
+    }
+
+    if let Some(size) = data_length {
+        if size as usize != body_bytes.len() {
+            let err_msg = format!("Data length {} is not same with the size of the body {}",
+                                  size,
+                                  body_bytes.len());
+            warn!("{}: {}", id, err_msg);
+            return Err(Id3Error::new(error::ErrorKind::DataLength, err_msg)
+                .with_frame_id(id));
+        }
+    }
+
+    Ok(body_bytes)
+}
 
+///
+/// The decompressed size of 2.3 or the data length indicator of 2.4 in the bytes of a frame.
+/// It is `None` if the flag is not set or the bytes are too short.
+///
+pub(crate) fn frame_data_length(header: &FrameHeader, bytes: &[u8]) -> Option<u32> {
+    let mut header = header.clone();
+    header.read_flag_bytes(bytes);
+    header.data_length()
+}
+
+//
+// It read a frame header. the size of the frame must be in the remain bytes.
+//
+fn read_frame_header(readable: &mut Cursor<Vec<u8>>, version: u8) -> Result<FrameHeader> {
+    let header = read_frame_header_unchecked(readable, version)?;
+
+    check_frame_size(readable, &header.id(), header.size())?;
+
+    Ok(header)
+}
+
+//
+// It read a frame header even if the size overruns the tag.
+//
+fn read_frame_header_unchecked(readable: &mut Cursor<Vec<u8>>,
+                               version: u8)
+                               -> Result<FrameHeader> {
+    let position = Cursor::position(readable) as usize;
+
+    let mut header = match version {
+        2 => FrameHeader::V22(FrameHeaderV2::read(readable, 2, "")?),
+        3 => FrameHeader::V23(FrameHeaderV3::read(readable, 3, "")?),
+        _ => FrameHeader::V24(FrameHeaderV4::read(readable, 4, "")?),
+    };
+
+    if let FrameHeader::V24(ref mut header) = header {
+        if let Some(size) = plain_frame_size(readable.get_ref(), position) {
+            header.size = size;
+        }
     }
-}
\ No newline at end of file
+
+    Ok(header)
+}
+
+//
+// It decompress a frame. a zlib error is a error of the frame, so the bytes that are
+// decompressed until the failure are not used.
+//
+fn decompress(id: &str, bytes: &Vec<u8>, max: usize) -> Result<Vec<u8>> {
+    let decoder = ZlibDecoder::new(&bytes[..]);
+    let mut out = vec![];
+
+    //
+    // It read one more byte than the limit to know if the output is larger than the limit.
+    //
+    let result = decoder.take((max as u64).saturating_add(1)).read_to_end(&mut out);
+
+    check_limit("Decompressed size", out.len(), max)
+        .map_err(|e| e.with_frame_id(id))?;
+
+    if let Err(e) = result {
+        warn!("'{}' decompression error: {}", id, e);
+        return Err(Id3Error::new(error::ErrorKind::Decompression, e.to_string())
+            .with_frame_id(id));
+    }
+
+    Ok(out)
+}
+
+//
+// A size must not be larger than the limit.
+//
+pub(crate) fn check_limit(name: &str, size: usize, limit: usize) -> Result<()> {
+    if size > limit {
+        let err_msg = format!("{} {} is larger than the limit {}", name, size, limit);
+        warn!("{}", err_msg);
+        return Err(Id3Error::new(error::ErrorKind::LimitExceeded, err_msg));
+    }
+
+    Ok(())
+}
+
+//
+// The size of a frame must not be larger than the remain bytes of the tag.
+//
+fn check_frame_size(readable: &Cursor<Vec<u8>>, id: &str, size: u32) -> Result<()> {
+    let remain = (readable.get_ref().len() as u64).saturating_sub(readable.position());
+
+    if size as u64 > remain {
+        let err_msg = format!("Frame size {} is larger than the remain bytes {}", size, remain);
+        warn!("{}: {}", id, err_msg);
+        return Err(Id3Error::new(error::ErrorKind::FrameTooLarge, err_msg)
+            .with_frame_id(id));
+    }
+
+    Ok(())
+}
+
+//
+// CRC-32 of extended header. (ISO-3309)
+//
+fn crc32<'a, I>(bytes: I) -> u32
+    where I: Iterator<Item = &'a u8>
+{
+    let mut crc = 0xffffffffu32;
+
+    for b in bytes {
+        crc = crc ^ (*b as u32);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
@@ -0,0 +1,326 @@
+//!
+//! Zero-copy reading of the frames from a byte slice. e.g) a memory-mapped file.
+//!
+//! The frame headers are read from the slice, and a frame body borrows the slice. it is copied
+//! only if the tag or the frame is unsynchronised, or the frame is compressed.
+//! `MappedFile` is enabled with the `mmap` feature.
+//!
+#[cfg(feature = "mmap")]
+extern crate memmap2;
+
+use error::{self, Id3Error, Result};
+use frame::*;
+use frame::types::*;
+use metadata::{check_limit, frame_content, is_frame_id, plain_frame_size, LazyFrame, Limits,
+               ParseOptions, ReadOp, Unit};
+use rw::Readable;
+
+use std::borrow::Cow;
+#[cfg(feature = "mmap")]
+use std::fs::File;
+use std::io::Cursor;
+use std::iter::Iterator;
+use std::str;
+
+///
+/// A frame that borrows the body from the slice.
+///
+/// - offset: the position of the frame header. it is the one of the synchronised bytes if the
+///   tag is unsynchronised.
+/// - bytes: the frame body as it is in the tag. same with `LazyFrame::bytes`.
+/// - limits: the limits that the frames are read with. same with `LazyFrame::limits`.
+/// - plain_size: the size of a 2.4 frame is not synchsafe. same with `LazyFrame::plain_size`.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct BorrowedFrame<'a> {
+    pub header: FrameHeader,
+    pub offset: usize,
+    pub bytes: Cow<'a, [u8]>,
+    pub limits: Limits,
+    pub plain_size: bool,
+}
+
+impl<'a> BorrowedFrame<'a> {
+    pub fn version(&self) -> u8 {
+        match self.header {
+            FrameHeader::V22(_) => 2,
+            FrameHeader::V23(_) => 3,
+            FrameHeader::V24(_) => 4,
+        }
+    }
+
+    ///
+    /// The body without the extra bytes of the flags. it is synchronised and decompressed if
+    /// the flags are set. the decompressed size must not be larger than
+    /// `Limits::max_decompressed_size` of the options.
+    ///
+    pub fn content(&self, options: &ParseOptions) -> Result<Cow<'a, [u8]>> {
+        match self.bytes {
+            Cow::Borrowed(bytes) => frame_content(&self.header, bytes, options),
+            Cow::Owned(ref bytes) => {
+                let content = frame_content(&self.header, bytes, options)?;
+                Ok(Cow::Owned(content.into_owned()))
+            }
+        }
+    }
+
+    ///
+    /// The text of a text information frame. it is `None` for the other frames.
+    ///
+    /// It borrows the slice if the text is ISO-8859-1 in ASCII range or UTF-8, and there is
+    /// nothing to trim. the text is same with `TEXT::text`.
+    ///
+    pub fn text(&self, options: &ParseOptions) -> Result<Option<Cow<'a, str>>> {
+        let id = self.header.id();
+        if !id.starts_with("T") || id == id::TXXX || id == id::TXX {
+            return Ok(None);
+        }
+
+        if self.header.has_flag(FrameHeaderFlag::Encryption) {
+            return Ok(None);
+        }
+
+        let content = self.content(options)?;
+
+        let borrowed = match content {
+            Cow::Borrowed(bytes) if bytes.len() > 0 => borrowed_text(bytes),
+            _ => None,
+        };
+
+        if let Some(text) = borrowed {
+            return Ok(Some(Cow::Borrowed(text)));
+        }
+
+        let text = TEXT::read(&mut Cursor::new(content.into_owned()), self.version(), &id)?;
+
+        Ok(Some(Cow::Owned(text.text)))
+    }
+
+    ///
+    /// It copy the body and return a `LazyFrame`. the limits are kept.
+    ///
+    pub fn to_lazy(&self) -> LazyFrame {
+        LazyFrame {
+            header: self.header.clone(),
+            bytes: self.bytes.to_vec(),
+            limits: self.limits.clone(),
+            plain_size: self.plain_size,
+        }
+    }
+
+    ///
+    /// It decode the frame body with the options. same with `LazyFrame::decode_with`.
+    ///
+    pub fn decode(&self, options: &ParseOptions) -> Result<FrameBody> {
+        self.to_lazy().decode_with(options)
+    }
+}
+
+//
+// The text that can be borrowed. the first byte is the text encoding.
+// the trimmed characters are same with `TEXT`.
+//
+fn borrowed_text(bytes: &[u8]) -> Option<&str> {
+    let data = &bytes[1..];
+
+    let text = match bytes[0] {
+        0 if data.iter().all(|b| *b < 0x80) => str::from_utf8(data).ok(),
+        3 => str::from_utf8(data).ok(),
+        _ => None,
+    };
+
+    text.map(|text| text.trim()).and_then(|text| {
+        let trimmed = |c: char| c == '\u{0}' || c == '\u{feff}' || c == '\u{fffe}' || c == '|';
+        let needs_trim = text.starts_with(&trimmed) || text.ends_with(&trimmed);
+
+        if needs_trim { None } else { Some(text) }
+    })
+}
+
+///
+/// A iterator of the frames of the tag at the first of the slice.
+///
+pub struct BorrowedFrames<'a> {
+    head: Head,
+    tag: Cow<'a, [u8]>,
+    position: usize,
+    done: bool,
+    limits: Limits,
+    frame_count: usize,
+}
+
+impl<'a> BorrowedFrames<'a> {
+    pub fn head(&self) -> &Head {
+        &self.head
+    }
+
+    //
+    // If a frame id is at the position. the rule is same with `MetadataReader`.
+    //
+    fn frame_exist(&self) -> bool {
+        let bytes = &self.tag[self.position..];
+
+        bytes.len() >= 3 && is_frame_id(&bytes[0..3])
+    }
+
+    fn read_frame(&mut self) -> Result<BorrowedFrame<'a>> {
+        let offset = 10 + self.position;
+        let max_frames = self.limits.max_frames;
+        check_limit("Frame count", self.frame_count + 1, max_frames)
+            .map_err(|e| e.with_offset(offset))?;
+        self.frame_count = self.frame_count + 1;
+
+        let version = self.head.version;
+        let mut readable = Cursor::new(&self.tag[self.position..]);
+
+        let mut header = match version {
+            2 => FrameHeader::V22(FrameHeaderV2::read(&mut readable, 2, "")?),
+            3 => FrameHeader::V23(FrameHeaderV3::read(&mut readable, 3, "")?),
+            _ => FrameHeader::V24(FrameHeaderV4::read(&mut readable, 4, "")?),
+        };
+
+        let mut plain_size = false;
+        if let FrameHeader::V24(ref mut header) = header {
+            if let Some(size) = plain_frame_size(&self.tag, self.position) {
+                header.size = size;
+                plain_size = true;
+            }
+        }
+
+        check_limit("Frame size", header.size() as usize, self.limits.max_frame_size)
+            .map_err(|e| e.with_offset(offset).with_frame_id(header.id()))?;
+
+        let start = self.position + readable.position() as usize;
+        let end = start + header.size() as usize;
+
+        if end > self.tag.len() {
+            let err_msg = format!("Frame size {} is larger than the remain bytes {}",
+                                  header.size(),
+                                  self.tag.len() - start);
+            warn!("{}: {}", header.id(), err_msg);
+            return Err(Id3Error::new(error::ErrorKind::FrameTooLarge, err_msg)
+                .with_offset(offset)
+                .with_frame_id(header.id()));
+        }
+
+        let bytes = match self.tag {
+            Cow::Borrowed(tag) => Cow::Borrowed(&tag[start..end]),
+            Cow::Owned(ref tag) => Cow::Owned(tag[start..end].to_vec()),
+        };
+
+        header.read_flag_bytes(&bytes);
+
+        let frame = BorrowedFrame {
+            header: header,
+            offset: offset,
+            bytes: bytes,
+            limits: self.limits.clone(),
+            plain_size: plain_size,
+        };
+        self.position = end;
+
+        Ok(frame)
+    }
+}
+
+impl<'a> Iterator for BorrowedFrames<'a> {
+    type Item = Result<BorrowedFrame<'a>>;
+
+    ///
+    /// It stop at the padding. after a error, it stop.
+    ///
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || !self.frame_exist() {
+            return None;
+        }
+
+        let frame = self.read_frame();
+        self.done = frame.is_err();
+
+        Some(frame)
+    }
+}
+
+///
+/// It read the header at the first of 'bytes' and return the frames of the tag.
+/// the sizes and the number of the frames are limited by `ParseOptions::limits`.
+///
+pub fn frames<'a>(bytes: &'a [u8], options: &ParseOptions) -> Result<BorrowedFrames<'a>> {
+    let head = match Cursor::new(bytes).head()? {
+        Unit::Header(head) => head,
+        _ => {
+            let err_msg = "Invalid header";
+            warn!("{}", err_msg);
+            return Err(Id3Error::new(error::ErrorKind::InvalidHeader, err_msg));
+        }
+    };
+
+    check_limit("Tag size", head.size as usize, options.limits.max_tag_size)?;
+
+    let tag_end = 10 + head.size as usize;
+    if tag_end > bytes.len() {
+        let err_msg = "Invalid tag size";
+        warn!("{}: {}", err_msg, head.size);
+        return Err(Id3Error::new(error::ErrorKind::FrameTooLarge, err_msg));
+    }
+
+    //
+    // If the tag is unsynchronised, the synchronised bytes are copied.
+    //
+    let tag = if head.has_flag(HeadFlag::Unsynchronisation) {
+        let len = head.size as usize;
+        Cow::Owned(Cursor::new(&bytes[10..tag_end]).to_synchronize(len)?)
+    } else {
+        Cow::Borrowed(&bytes[10..tag_end])
+    };
+
+    let position = if head.has_flag(HeadFlag::ExtendedHeader) {
+        let mut readable = Cursor::new(&tag[..]);
+        ExtendedHeader::read(&mut readable, head.version)?;
+        readable.position() as usize
+    } else {
+        0
+    };
+
+    Ok(BorrowedFrames {
+        head: head,
+        tag: tag,
+        position: position,
+        done: false,
+        limits: options.limits.clone(),
+        frame_count: 0,
+    })
+}
+
+///
+/// A memory-mapped file. the file must not be modified while it is mapped.
+///
+#[cfg(feature = "mmap")]
+pub struct MappedFile {
+    map: memmap2::Mmap,
+}
+
+#[cfg(feature = "mmap")]
+impl MappedFile {
+    pub fn open(path: &str) -> Result<Self> {
+        let file = File::open(path)?;
+
+        //
+        // It is unsafe because the other process can modify the file.
+        //
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+
+        Ok(MappedFile { map: map })
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.map[..]
+    }
+
+    ///
+    /// The frames of the tag at the first of the file.
+    ///
+    pub fn frames<'a>(&'a self, options: &ParseOptions) -> Result<BorrowedFrames<'a>> {
+        frames(self.as_bytes(), options)
+    }
+}
@@ -1,19 +1,20 @@
 use std::cmp;
-use std::convert::AsRef;
 use std::fs::File;
 use std::boxed::Box;
-use std::io::{self, Cursor, Error, ErrorKind, Read, Seek, SeekFrom, Result, Write};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 use std::vec::Vec;
 
+use error::{ErrorKind, Id3Error, Result};
+
 const DEFAULT_BUF_SIZE: usize = 1024;
 
-type BytesResult = io::Result<Vec<u8>>;
-type UnsignedByteResult = io::Result<u8>;
-type Unsigned16Result = io::Result<u16>;
-type Unsigned32Result = io::Result<u32>;
-type StringResult = io::Result<String>;
-type UnsignedIntResult = io::Result<usize>;
-type VoidResult = io::Result<()>;
+type BytesResult = Result<Vec<u8>>;
+type UnsignedByteResult = Result<u8>;
+type Unsigned16Result = Result<u16>;
+type Unsigned32Result = Result<u32>;
+type StringResult = Result<String>;
+type UnsignedIntResult = Result<usize>;
+type VoidResult = Result<()>;
 
 pub trait Readable: Read + Seek {
     fn all_bytes(&mut self) -> BytesResult {
@@ -53,7 +54,7 @@ pub trait Readable: Read + Seek {
             if read == 0 && buf.len() > 0 {
                 let err_msg = format!("read try: {}: but fail. (buf size: {})", amount, buf.len());
                 warn!("{}", err_msg);
-                return Err(Error::new(ErrorKind::Other, err_msg));
+                return Err(Id3Error::new(ErrorKind::UnexpectedEof, err_msg));
             }
 
             ret.append(&mut buf);
@@ -215,10 +216,14 @@ pub trait Readable: Read + Seek {
     }
 
     fn look_string(&mut self, amount: usize) -> StringResult {
-        let v = self.read_string(amount)?;
-        let _ = self.skip_bytes((amount as isize) * -1)?;
+        //
+        // The position is restored even if it read less than 'amount'.
+        //
+        let curr_pos = self.skip_bytes(0)?;
+        let v = self.read_string(amount);
+        let _ = self.position(curr_pos)?;
 
-        Ok(v)
+        v
     }
 
     fn look_u8(&mut self) -> UnsignedByteResult {
@@ -482,8 +487,11 @@ pub trait Writable: Readable + Write {
     }
 }
 
-impl Readable for File {}
-impl<T> Readable for Cursor<T> where T: AsRef<[u8]> {}
+//
+// Every `Read + Seek` is `Readable` since 0.4.0. it is a breaking change because an impl for a
+// type of the user conflicts with it.
+//
+impl<T> Readable for T where T: Read + Seek {}
 
 impl Writable for File {}
 impl<'a> Writable for Cursor<&'a mut [u8]> {}
@@ -2,6 +2,8 @@
 extern crate log;
 extern crate env_logger;
 extern crate tempdir;
+#[cfg(feature = "async")]
+extern crate futures;
 
 extern crate rtag;
 
@@ -12,11 +14,80 @@ use std::fs;
 use std::io::Cursor;
 use std::vec::Vec;
 
+use rtag::error;
 use rtag::frame::*;
 use rtag::frame::types::*;
 use rtag::metadata::*;
 use rtag::rw::*;
 
+//
+// The bytes of a tag. the size in the header is computed from the frames, and the footer is
+// appended if the footer flag is set in 2.4. a frame is any bytes, so a broken one can be given.
+//
+fn build_tag(version: u8, flag: u8, frames: &[&[u8]]) -> Vec<u8> {
+    let body = frames.concat();
+    let size = synchsafe(body.len() as u32);
+
+    let mut bytes = vec![0x49, 0x44, 0x33, version, 0x00, flag];
+    bytes.extend_from_slice(&size);
+    bytes.extend_from_slice(&body);
+    if version == 4 && flag & 0x10 != 0 {
+        bytes.extend_from_slice(&[0x33, 0x44, 0x49, version, 0x00, flag]);
+        bytes.extend_from_slice(&size);
+    }
+
+    bytes
+}
+
+//
+// The bytes of a frame. the size is computed from the body, and it is synchsafe only in 2.4.
+//
+fn build_frame(version: u8, id: &str, flag: u16, body: &[u8]) -> Vec<u8> {
+    let size = body.len() as u32;
+
+    let mut bytes = id.as_bytes().to_vec();
+    match version {
+        2 => bytes.extend_from_slice(&size.to_be_bytes()[1..]),
+        3 => bytes.extend_from_slice(&size.to_be_bytes()),
+        _ => bytes.extend_from_slice(&synchsafe(size)),
+    }
+    if version > 2 {
+        bytes.extend_from_slice(&flag.to_be_bytes());
+    }
+    bytes.extend_from_slice(body);
+
+    bytes
+}
+
+//
+// The bytes of a text frame in ISO-8859-1.
+//
+fn build_text(version: u8, id: &str, text: &str) -> Vec<u8> {
+    let mut body = vec![0x00];
+    body.extend_from_slice(text.as_bytes());
+
+    build_frame(version, id, 0, &body)
+}
+
+//
+// The bytes of a frame1 that has only the "TAG" id.
+//
+fn build_frame1() -> Vec<u8> {
+    let mut bytes = vec![0x54, 0x41, 0x47];
+    bytes.resize(128, 0);
+
+    bytes
+}
+
+fn synchsafe(size: u32) -> [u8; 4] {
+    let mut bytes = [0; 4];
+    for i in 0..4 {
+        bytes[i] = (size >> (7 * (3 - i))) as u8 & 0x7f;
+    }
+
+    bytes
+}
+
 #[test]
 fn metadata_regex() {
     let frame_id = regex::Regex::new(r"^[A-Z][A-Z0-9]{2,}$").unwrap();
@@ -315,6 +386,9 @@ fn metadata_frame_tbpm() {
                     size: 5,
                     status_flag: 0,
                     encoding_flag: 0,
+                    data_length: None,
+                    group_symbol: None,
+                    encryption_method: None,
                 });
                 let new_frame_header = FrameHeader::V23(FrameHeaderV3::read(&mut readable, 3, "")
                     .unwrap());
@@ -646,10 +720,85 @@ fn metadata_v230_ext_header() {
 
         for m in MetadataReader::new(path).unwrap() {
             match m {
+                Unit::ExtendedHeader(ext_head) => {
+                    assert_eq!(10, ext_head.size);
+                    assert_eq!(Some(0), ext_head.padding_size);
+                    assert_eq!(Some(0x91d415a1), ext_head.crc32);
+                    assert_eq!(0, ext_head.extra.len());
+                }
                 Unit::FrameV2(_, FrameBody::TCON(frame)) => assert_eq!("(0)Blues", frame.text),
                 _ => (),
             }
         }
+
+        let units = MetadataReader::new(path).unwrap().collect::<Vec<Unit>>();
+        let (_, _, bytes) = MetadataWriter::new("").unwrap().to_bytes(units).unwrap();
+
+        let i = MetadataReader::from_bytes(&bytes)
+            .unwrap()
+            .filter(|m| match m {
+                &Unit::ExtendedHeader(ref ext_head) => ext_head.crc32 == Some(0x91d415a1),
+                _ => false,
+            });
+
+        assert!(i.count() == 1);
+    }
+}
+
+#[test]
+fn metadata_v240_ext_header() {
+    let _ = env_logger::init();
+
+    // size, number of flag bytes, flags: update, crc, restrictions
+    let ext_head = [0x00, 0x00, 0x00, 0x0f, 0x01, 0x70, 0x00, 0x05, 0x01, 0x02, 0x03, 0x04, 0x05,
+                    0x01, 0x84];
+    let title = build_text(4, "TIT2", "AB");
+    let bytes = build_tag(4, 0x40, &[&ext_head, &title]);
+
+    let units = MetadataReader::from_bytes(&bytes).unwrap().collect::<Vec<Unit>>();
+    assert_eq!(3, units.len());
+
+    match &units[1] {
+        &Unit::ExtendedHeader(ref ext_head) => {
+            assert_eq!(15, ext_head.size);
+            assert_eq!(vec![0x70], ext_head.flags);
+            assert!(ext_head.tag_is_update);
+            assert_eq!(Some(0x1040c205), ext_head.crc32);
+            assert_eq!(Some(0x84), ext_head.restrictions);
+            assert_eq!(None, ext_head.padding_size);
+        }
+        _ => assert!(false),
+    }
+
+    match &units[2] {
+        &Unit::FrameV2(_, FrameBody::TIT2(ref frame)) => assert_eq!("AB", frame.text),
+        _ => assert!(false),
+    }
+
+    let (_, _, written) = MetadataWriter::new("").unwrap().to_bytes(units).unwrap();
+    assert_eq!(bytes.len(), written.len());
+
+    match MetadataReader::from_bytes(&written).unwrap().nth(1) {
+        Some(Unit::ExtendedHeader(ext_head)) => {
+            assert!(ext_head.tag_is_update);
+            assert_eq!(Some(0x84), ext_head.restrictions);
+            assert!(ext_head.crc32.is_some());
+            assert!(ext_head.crc32 != Some(0x1040c205));
+        }
+        _ => assert!(false),
+    }
+
+    // the CRC of 2.4 covers the frames and the padding.
+    let bytes = build_tag(4, 0x40, &[&ext_head, &title, &[0x00; 6]]);
+
+    let units = MetadataReader::from_bytes(&bytes).unwrap().collect::<Vec<Unit>>();
+    let writer = MetadataWriter::new("").unwrap().with_round_trip(true);
+    let (_, _, written) = writer.to_bytes(units).unwrap();
+    assert_eq!(bytes.len(), written.len());
+
+    match MetadataReader::from_bytes(&written).unwrap().nth(1) {
+        Some(Unit::ExtendedHeader(ext_head)) => assert_eq!(Some(0x213eb437), ext_head.crc32),
+        _ => assert!(false),
     }
 }
 
@@ -985,6 +1134,9 @@ fn metadata_writer() {
                                       size: 0,
                                       status_flag: 0,
                                       encoding_flag: 0,
+                                      data_length: None,
+                                      group_symbol: None,
+                                      encryption_method: None,
                                   }),
                                   FrameBody::TALB(TEXT {
                                       text_encoding: TextEncoding::UTF8,
@@ -1145,6 +1297,1344 @@ fn frame_encoding() {
     assert_eq!(0, data.len());
 }
 
+#[test]
+fn metadata_from_reader() {
+    let _ = env_logger::init();
+
+    fn units_of_file(path: &str) -> Vec<String> {
+        MetadataReader::new(path).unwrap().map(|unit| format!("{:?}", unit)).collect()
+    }
+
+    for path in vec!["./test-resources/240.mp3",
+                     "./test-resources/v1-v2.mp3",
+                     "./test-resources/v2.3-unsync.mp3",
+                     "./test-resources/v1-utf8.mp3",
+                     "./test-resources/230-no-id3.mp3"] {
+        let expected = units_of_file(path);
+        assert!(expected.len() > 0 || path == "./test-resources/230-no-id3.mp3");
+
+        let bytes = fs::read(path).unwrap();
+
+        let from_bytes = MetadataReader::from_bytes(&bytes)
+            .unwrap()
+            .map(|unit| format!("{:?}", unit))
+            .collect::<Vec<_>>();
+        assert_eq!(expected, from_bytes);
+
+        let from_reader = MetadataReader::from_reader(Cursor::new(bytes.clone()))
+            .unwrap()
+            .map(|unit| format!("{:?}", unit))
+            .collect::<Vec<_>>();
+        assert_eq!(expected, from_reader);
+    }
+}
+
+#[test]
+fn metadata_v240_footer() {
+    let _ = env_logger::init();
+
+    let title = build_text(4, "TIT2", "AB");
+    let bytes = build_tag(4, 0x10, &[&title]);
+
+    let units = MetadataReader::from_bytes(&bytes).unwrap().collect::<Vec<Unit>>();
+    assert_eq!(3, units.len());
+
+    match &units[2] {
+        &Unit::Footer(ref footer) => {
+            assert_eq!("3DI", footer.tag_id);
+            assert_eq!(13, footer.size);
+            assert!(footer.has_flag(HeadFlag::FooterPresent));
+        }
+        _ => assert!(false),
+    }
+
+    let (_, head_size, written) = MetadataWriter::new("").unwrap().to_bytes(units).unwrap();
+    assert_eq!(23, head_size);
+    assert_eq!(bytes, written);
+
+    // footer is created from the head.
+    let units = vec![Unit::Header(Head {
+                         tag_id: "ID3".to_string(),
+                         version: 4,
+                         minor_version: 0,
+                         flag: 0x10,
+                         size: 0,
+                     }),
+                     Unit::FrameV2(FrameHeader::V24(FrameHeaderV4 {
+                                       id: "TIT2".to_string(),
+                                       size: 0,
+                                       status_flag: 0,
+                                       encoding_flag: 0,
+                                       data_length: None,
+                                       group_symbol: None,
+                                       encryption_method: None,
+                                   }),
+                                   FrameBody::TIT2(TEXT {
+                                       text_encoding: TextEncoding::ISO88591,
+                                       text: "AB".to_string(),
+                                   }))];
+    let (_, _, written) = MetadataWriter::new("").unwrap().to_bytes(units).unwrap();
+    assert_eq!(bytes, written);
+
+    // the padding is not written with the footer even in round-trip mode.
+    let padded = build_tag(4, 0x10, &[&title, &[0x00; 4]]);
+
+    let units = MetadataReader::from_bytes(&padded)
+        .unwrap()
+        .with_options(ParseOptions { lazy: true, ..ParseOptions::default() })
+        .collect::<Vec<Unit>>();
+    assert!(units.iter().any(|unit| match unit {
+        &Unit::Padding { size: 4, .. } => true,
+        _ => false,
+    }));
+
+    let writer = MetadataWriter::new("").unwrap().with_round_trip(true);
+    let (_, _, written) = writer.to_bytes(units).unwrap();
+    assert_eq!(bytes, written);
+
+    // footer is different from the head.
+    let mut invalid = bytes.clone();
+    let len = invalid.len();
+    invalid[len - 1] = 0x0c;
+    let i = MetadataReader::from_bytes(&invalid)
+        .unwrap()
+        .filter(|m| match m {
+            &Unit::Footer(_) => true,
+            _ => false,
+        });
+
+    assert_eq!(0, i.count());
+}
+
+#[test]
+fn metadata_padding() {
+    let _ = env_logger::init();
+
+    let mut head_size = 0;
+    let mut paddings = vec![];
+    for m in MetadataReader::new("./test-resources/240.mp3").unwrap() {
+        match m {
+            Unit::Header(head) => head_size = head.size as usize,
+            Unit::Padding { offset, size, all_zero, .. } => paddings.push((offset, size, all_zero)),
+            _ => (),
+        }
+    }
+
+    assert_eq!(1, paddings.len());
+    let (offset, size, all_zero) = paddings[0];
+    assert!(size > 0);
+    assert!(all_zero);
+    assert_eq!(10 + head_size, offset + size);
+
+    let padding = [0x00, 0x00, 0x00, 0x00, 0x61, 0x00];
+    let bytes = build_tag(4, 0, &[&build_text(4, "TIT2", "AB"), &padding]);
+
+    match MetadataReader::from_bytes(&bytes).unwrap().last() {
+        Some(Unit::Padding { offset, size, all_zero, bytes }) => {
+            assert_eq!(23, offset);
+            assert_eq!(6, size);
+            assert!(!all_zero);
+            assert_eq!(vec![0x00, 0x00, 0x00, 0x00, 0x61, 0x00], bytes);
+        }
+        _ => assert!(false),
+    }
+}
+
+#[test]
+fn metadata_v240_appended_tag() {
+    let _ = env_logger::init();
+
+    // the SEEK frame point to the tag that is 4 bytes after.
+    let mut bytes = build_tag(4, 0, &[&build_frame(4, "SEEK", 0, &[0x00, 0x00, 0x00, 0x04])]);
+    bytes.extend_from_slice(&[0xff, 0xfb, 0x90, 0x00]);
+    bytes.extend_from_slice(&build_tag(4, 0, &[&build_text(4, "TIT2", "CD")]));
+    bytes.extend_from_slice(&[0xff, 0xfb, 0x90, 0x00]);
+
+    // the appended tag is located by the footer that is just before the frame1.
+    bytes.extend_from_slice(&build_tag(4, 0x10, &[&build_text(4, "TIT2", "AB")]));
+    bytes.extend_from_slice(&build_frame1());
+
+    let units = MetadataReader::from_bytes(&bytes).unwrap().collect::<Vec<Unit>>();
+    assert_eq!(8, units.len());
+
+    match &units[1] {
+        &Unit::FrameV2(_, FrameBody::SEEK(ref frame)) => assert_eq!(4, frame.offset_to_next_tag),
+        _ => assert!(false),
+    }
+
+    let titles = units.iter()
+        .filter_map(|unit| match unit {
+            &Unit::FrameV2(_, FrameBody::TIT2(ref frame)) => Some(frame.text.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["CD", "AB"], titles);
+
+    let headers = units.iter()
+        .filter(|unit| match unit {
+            &&Unit::Header(_) => true,
+            _ => false,
+        })
+        .count();
+    assert_eq!(3, headers);
+
+    match &units[6] {
+        &Unit::Footer(_) => (),
+        _ => assert!(false),
+    }
+
+    match &units[7] {
+        &Unit::FrameV1(_) => (),
+        _ => assert!(false),
+    }
+}
+
+#[test]
+fn metadata_try_iter() {
+    let _ = env_logger::init();
+
+    // the size of the second frame is larger than the tag.
+    let too_large = [0x54, 0x49, 0x54, 0x33, 0x00, 0x00, 0x00, 0x7f, 0x00, 0x00, 0x00, 0x43];
+    let mut bytes = build_tag(4, 0, &[&build_text(4, "TIT2", "AB"), &too_large]);
+    bytes.extend_from_slice(&build_frame1());
+
+    let mut reader = MetadataReader::from_bytes(&bytes).unwrap();
+    let results = reader.try_iter().collect::<Vec<_>>();
+    assert_eq!(4, results.len());
+
+    match results[1] {
+        Ok(Unit::FrameV2(_, FrameBody::TIT2(ref frame))) => assert_eq!("AB", frame.text),
+        _ => assert!(false),
+    }
+    assert!(results[2].is_err());
+    match results[3] {
+        Ok(Unit::FrameV1(_)) => (),
+        _ => assert!(false),
+    }
+
+    // the Iterator skip the broken frame.
+    let units = MetadataReader::from_bytes(&bytes).unwrap().collect::<Vec<_>>();
+    assert_eq!(3, units.len());
+}
+
+#[test]
+fn metadata_error_kind() {
+    let _ = env_logger::init();
+
+    // the size of the second frame is larger than the tag.
+    let too_large = [0x54, 0x49, 0x54, 0x33, 0x00, 0x00, 0x00, 0x7f, 0x00, 0x00, 0x00, 0x43];
+    let bytes = build_tag(4, 0, &[&build_text(4, "TIT2", "AB"), &too_large]);
+
+    let mut reader = MetadataReader::from_bytes(&bytes).unwrap();
+    match reader.try_iter().find(|result| result.is_err()) {
+        Some(Err(e)) => {
+            assert_eq!(error::ErrorKind::FrameTooLarge, e.kind());
+            assert_eq!(Some(23), e.offset());
+            assert_eq!(Some("TIT3"), e.frame_id());
+        }
+        _ => assert!(false),
+    }
+
+    // a frame of version 3 in a tag of version 4.
+    let units = vec![Unit::Header(Head {
+                         tag_id: "ID3".to_string(),
+                         version: 4,
+                         minor_version: 0,
+                         flag: 0,
+                         size: 0,
+                     }),
+                     Unit::FrameV2(FrameHeader::V23(FrameHeaderV3 {
+                                       id: "TIT2".to_string(),
+                                       size: 0,
+                                       status_flag: 0,
+                                       encoding_flag: 0,
+                                       data_length: None,
+                                       group_symbol: None,
+                                       encryption_method: None,
+                                   }),
+                                   FrameBody::TIT2(TEXT {
+                                       text_encoding: TextEncoding::ISO88591,
+                                       text: "AB".to_string(),
+                                   }))];
+    let e = MetadataWriter::new("").unwrap().write(units, false).unwrap_err();
+    assert_eq!(error::ErrorKind::VersionMismatch, e.kind());
+
+    // the source is shorter than the bytes that are read.
+    let e = Cursor::new(vec![0x49, 0x44]).read_bytes(4).unwrap_err();
+    assert_eq!(error::ErrorKind::UnexpectedEof, e.kind());
+    let e = Cursor::new(vec![0x49, 0x44, 0x33, 0x04]).head().unwrap_err();
+    assert_eq!(error::ErrorKind::UnexpectedEof, e.kind());
+}
+
+#[test]
+fn metadata_parse_options() {
+    let _ = env_logger::init();
+
+    // a text that is not UTF-8 and an unknown frame id.
+    let not_utf8 = build_frame(4, "TIT2", 0, &[0x03, 0xc3, 0x28]);
+    let unknown = build_text(4, "ZZZZ", "AB");
+    let bytes = build_tag(4, 0, &[&not_utf8, &unknown]);
+
+    let units = MetadataReader::from_bytes(&bytes).unwrap().collect::<Vec<_>>();
+    assert_eq!(3, units.len());
+    match units[1] {
+        Unit::FrameV2(_, FrameBody::TIT2(ref frame)) => assert_eq!("", frame.text),
+        _ => assert!(false),
+    }
+    match units[2] {
+        Unit::FrameV2(_, FrameBody::Unknown { ref id, ref raw }) => {
+            assert_eq!("ZZZZ", id);
+            assert_eq!(&vec![0x00, 0x41, 0x42], raw);
+        }
+        _ => assert!(false),
+    }
+
+    let mut reader = MetadataReader::from_bytes(&bytes)
+        .unwrap()
+        .with_options(ParseOptions { strict: true, ..ParseOptions::default() });
+    let errors = reader.try_iter()
+        .filter_map(|result| result.err())
+        .map(|e| (e.kind(), e.frame_id().unwrap_or("").to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(vec![(error::ErrorKind::Encoding, "TIT2".to_string())], errors);
+
+    // the frames after a error are not read. so the unknown frame id is checked alone.
+    let bytes = build_tag(4, 0, &[&unknown]);
+
+    let mut reader = MetadataReader::from_bytes(&bytes)
+        .unwrap()
+        .with_options(ParseOptions { strict: true, ..ParseOptions::default() });
+    match reader.try_iter().nth(1) {
+        Some(Err(e)) => {
+            assert_eq!(error::ErrorKind::InvalidHeader, e.kind());
+            assert_eq!(Some("ZZZZ"), e.frame_id());
+        }
+        _ => assert!(false),
+    }
+}
+
+#[test]
+fn metadata_diagnostics() {
+    let _ = env_logger::init();
+
+    // a text that is not UTF-8, an unknown frame id and junk in the padding.
+    let not_utf8 = build_frame(4, "TIT2", 0, &[0x03, 0xc3, 0x28]);
+    let bytes = build_tag(4, 0, &[&not_utf8, &build_text(4, "ZZZZ", "AB"), &[0x00, 0x01]]);
+
+    let mut reader = MetadataReader::from_bytes(&bytes).unwrap();
+    assert_eq!(4, reader.by_ref().count());
+
+    let diagnostics = reader.diagnostics()
+        .iter()
+        .map(|d| (d.severity, d.kind, d.offset, d.frame_id.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(vec![(error::Severity::Warning,
+                     error::ErrorKind::Encoding,
+                     Some(10),
+                     Some("TIT2".to_string())),
+                    (error::Severity::Warning,
+                     error::ErrorKind::InvalidHeader,
+                     Some(23),
+                     Some("ZZZZ".to_string())),
+                    (error::Severity::Warning, error::ErrorKind::InvalidPadding, Some(36), None)],
+               diagnostics);
+
+    // a frame that can not be read.
+    let too_large = [0x54, 0x49, 0x54, 0x32, 0x00, 0x00, 0x00, 0x7f, 0x00, 0x00, 0x00, 0x41, 0x42];
+    let bytes = build_tag(4, 0, &[&too_large]);
+
+    let mut reader = MetadataReader::from_bytes(&bytes).unwrap();
+    assert_eq!(1, reader.by_ref().count());
+    assert_eq!(1, reader.diagnostics().len());
+    assert_eq!(error::Severity::Error, reader.diagnostics()[0].severity);
+    assert_eq!(error::ErrorKind::FrameTooLarge, reader.diagnostics()[0].kind);
+
+    // a frame body is read once, and the violations are kept in lenient mode.
+    let mut violations = error::Violations::new(false);
+    match read_framebody("TIT2", 4, Cursor::new(vec![0x05, 0x41]), &mut violations).unwrap() {
+        FrameBody::TIT2(frame) => assert_eq!("A", frame.text),
+        _ => assert!(false),
+    }
+    let kinds = violations.warnings.iter().map(|w| w.kind()).collect::<Vec<_>>();
+    assert_eq!(vec![error::ErrorKind::Encoding], kinds);
+
+    let mut violations = error::Violations::new(true);
+    assert!(read_framebody("TIT2", 4, Cursor::new(vec![0x05, 0x41]), &mut violations).is_err());
+}
+
+#[test]
+fn metadata_lazy_frame() {
+    let _ = env_logger::init();
+
+    for path in vec!["./test-resources/240.mp3",
+                     "./test-resources/v2.2.mp3",
+                     "./test-resources/v2.3-encrypted-frame.mp3",
+                     "./test-resources/v2.4-unsync.mp3"] {
+        let frames = MetadataReader::new(path)
+            .unwrap()
+            .filter_map(|unit| match unit {
+                Unit::FrameV2(head, body) => Some((head, body)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert!(frames.len() > 0);
+
+        let lazy_frames = MetadataReader::new(path)
+            .unwrap()
+            .with_options(ParseOptions { lazy: true, ..ParseOptions::default() })
+            .filter_map(|unit| match unit {
+                Unit::FrameV2(_, _) => panic!("a frame is decoded"),
+                Unit::LazyFrameV2(frame) => Some((frame.header.clone(), frame.decode().unwrap())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(frames, lazy_frames);
+    }
+}
+
+#[test]
+fn metadata_with_frame_ids() {
+    let _ = env_logger::init();
+
+    for path in vec!["./test-resources/240.mp3", "./test-resources/v2.2.mp3"] {
+        let expected = MetadataReader::new(path)
+            .unwrap()
+            .filter(|unit| match unit {
+                &Unit::FrameV2(_, FrameBody::TIT2(_)) => true,
+                &Unit::FrameV2(_, FrameBody::TALB(_)) => true,
+                &Unit::FrameV2(_, _) => false,
+                _ => true,
+            })
+            .map(|unit| format!("{:?}", unit))
+            .collect::<Vec<_>>();
+        assert!(expected.len() > 1);
+
+        let units = MetadataReader::new(path)
+            .unwrap()
+            .with_frame_ids(&[id::TIT2, id::TALB])
+            .map(|unit| format!("{:?}", unit))
+            .collect::<Vec<_>>();
+
+        assert_eq!(expected, units);
+    }
+}
+
+#[test]
+fn metadata_probe() {
+    let _ = env_logger::init();
+
+    let info = probe("./test-resources/240.mp3").unwrap();
+    assert_eq!(ProbeInfo {
+                   id3v2: true,
+                   tag_offset: 0,
+                   version: 4,
+                   minor_version: 0,
+                   flag: 0,
+                   size: 165126,
+                   audio_offset: 165136,
+                   id3v1: true,
+               },
+               info);
+
+    let info = probe("./test-resources/230-no-id3.mp3").unwrap();
+    assert!(!info.id3v2);
+    assert_eq!(0, info.audio_offset);
+    assert!(!info.id3v1);
+
+    // the footer is a part of the tag.
+    let bytes = build_tag(4, 0x10, &[&build_text(4, "TIT2", "AB")]);
+
+    let info = probe_reader(&mut Cursor::new(bytes)).unwrap();
+    assert_eq!(0x10, info.flag);
+    assert_eq!(13, info.size);
+    assert_eq!(33, info.audio_offset);
+}
+
+#[test]
+fn metadata_probe_search() {
+    let _ = env_logger::init();
+
+    let tag = build_tag(4, 0, &[&build_text(4, "TIT2", "AB")]);
+
+    // the tags that are stacked are in the audio offset, so all of them are replaced.
+    let mut bytes = tag.clone();
+    bytes.extend_from_slice(&tag);
+    bytes.extend_from_slice(&[0xff, 0xfb, 0x90]);
+
+    let info = probe_reader(&mut Cursor::new(bytes.clone())).unwrap();
+    assert_eq!(0, info.tag_offset);
+    assert_eq!(13, info.size);
+    assert_eq!(46, info.audio_offset);
+
+    let units = MetadataReader::from_bytes(&tag).unwrap().collect::<Vec<_>>();
+    let writer = MetadataWriter::new("").unwrap().with_round_trip(true);
+    let mut expected = tag.clone();
+    expected.extend_from_slice(&[0xff, 0xfb, 0x90]);
+    assert_eq!(expected, writer.to_file_bytes(units, false, &bytes).unwrap());
+
+    // the tag after junk is found only in the search window.
+    let mut bytes = vec![0x00, 0x00, 0x00, 0x00];
+    bytes.extend_from_slice(&tag);
+    bytes.extend_from_slice(&[0xff, 0xfb, 0x90]);
+
+    let info = probe_reader(&mut Cursor::new(bytes.clone())).unwrap();
+    assert!(!info.id3v2);
+
+    let options = ParseOptions { search_window: 16, ..ParseOptions::default() };
+    let info = probe_reader_with(&mut Cursor::new(bytes.clone()), &options).unwrap();
+    assert!(info.id3v2);
+    assert_eq!(4, info.tag_offset);
+    assert_eq!(27, info.audio_offset);
+
+    // the writer does not write another tag before the junk.
+    let units = MetadataReader::from_bytes(&tag).unwrap().collect::<Vec<_>>();
+    let writer = MetadataWriter::new("").unwrap().with_options(options);
+    match writer.to_file_bytes(units, false, &bytes) {
+        Err(e) => assert_eq!(error::ErrorKind::InvalidHeader, e.kind()),
+        Ok(_) => panic!("the tag after junk must not be written"),
+    }
+}
+
+#[test]
+fn metadata_resync() {
+    let _ = env_logger::init();
+
+    // the size of the second frame is larger than the tag but the third frame is valid.
+    let too_large = [0x54, 0x49, 0x54, 0x33, 0x00, 0x00, 0x00, 0x7f, 0x00, 0x00, 0x00, 0x43];
+    let album = build_text(4, "TALB", "DE");
+    let bytes = build_tag(4, 0, &[&build_text(4, "TIT2", "AB"), &too_large, &album]);
+
+    // without resync, the frames after the broken frame are lost.
+    let units = MetadataReader::from_bytes(&bytes).unwrap().collect::<Vec<_>>();
+    assert_eq!(2, units.len());
+
+    let mut reader = MetadataReader::from_bytes(&bytes)
+        .unwrap()
+        .with_options(ParseOptions { resync: true, ..ParseOptions::default() });
+    let results = reader.try_iter().collect::<Vec<_>>();
+    assert_eq!(4, results.len());
+    assert!(results[2].is_err());
+    match results[3] {
+        Ok(Unit::FrameV2(_, FrameBody::TALB(ref frame))) => assert_eq!("DE", frame.text),
+        _ => assert!(false),
+    }
+
+    let skipped = reader.diagnostics()
+        .iter()
+        .filter(|d| d.kind == error::ErrorKind::SkippedBytes)
+        .map(|d| (d.offset, d.message.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(vec![(Some(23), "12 bytes are skipped".to_string())], skipped);
+
+    // if there is no frame after the broken frame, the rest is the padding.
+    let bytes = build_tag(4, 0, &[&too_large]);
+
+    let units = MetadataReader::from_bytes(&bytes)
+        .unwrap()
+        .with_options(ParseOptions { resync: true, ..ParseOptions::default() })
+        .collect::<Vec<_>>();
+    assert_eq!(2, units.len());
+    match units[1] {
+        Unit::Padding { offset, size, all_zero, .. } => {
+            assert_eq!(10, offset);
+            assert_eq!(12, size);
+            assert!(!all_zero);
+        }
+        _ => assert!(false),
+    }
+}
+
+#[test]
+fn metadata_search_window() {
+    let _ = env_logger::init();
+
+    // 4 junk bytes, and then two tags stacked.
+    let mut bytes = vec![0x00, 0x01, 0x02, 0x03];
+    bytes.extend_from_slice(&build_tag(4, 0, &[&build_text(4, "TIT2", "AB")]));
+    bytes.extend_from_slice(&build_tag(4, 0, &[&build_text(4, "TIT2", "CD")]));
+
+    // the header must be at the first by default.
+    let units = MetadataReader::from_bytes(&bytes).unwrap().collect::<Vec<_>>();
+    assert_eq!(0, units.len());
+
+    let mut reader = MetadataReader::from_bytes(&bytes)
+        .unwrap()
+        .with_options(ParseOptions { search_window: 16, ..ParseOptions::default() });
+    let titles = reader.by_ref()
+        .filter_map(|unit| match unit {
+            Unit::FrameV2(_, FrameBody::TIT2(frame)) => Some(frame.text),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["AB".to_string(), "CD".to_string()], titles);
+    assert_eq!(&[4, 27], reader.tag_offsets());
+
+    let skipped = reader.diagnostics()
+        .iter()
+        .filter(|d| d.kind == error::ErrorKind::SkippedBytes)
+        .map(|d| (d.offset, d.message.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(vec![(Some(0), "4 bytes are skipped".to_string())], skipped);
+}
+
+#[test]
+fn metadata_layout() {
+    let _ = env_logger::init();
+
+    // the tag is unsynchronised, and 0xff of 'TPE1' is followed by 0x00 in the file.
+    let grouped = build_frame(3, "TIT2", 0x0020, &[0x01, 0x00, 0x41, 0x42]);
+    let unsynchronised = [0x54, 0x50, 0x45, 0x31, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0xff,
+                          0x00, 0x41];
+    let mut bytes = build_tag(3, 0x80, &[&grouped, &unsynchronised, &[0x00, 0x00]]);
+    bytes.extend_from_slice(&[0x01, 0x02, 0x03]);
+
+    let regions = layout_reader(&mut Cursor::new(bytes))
+        .unwrap()
+        .into_iter()
+        .map(|r| (r.kind, r.file_offset, r.file_size, r.tag_offset, r.tag_size))
+        .collect::<Vec<_>>();
+
+    assert_eq!(vec![(RegionKind::Header, 0, 10, None, None),
+                    (RegionKind::FrameHeader("TIT2".to_string()), 10, 10, Some(0), Some(10)),
+                    (RegionKind::GroupIdentity("TIT2".to_string()), 20, 1, Some(10), Some(1)),
+                    (RegionKind::FrameBody("TIT2".to_string()), 21, 3, Some(11), Some(3)),
+                    (RegionKind::FrameHeader("TPE1".to_string()), 24, 10, Some(14), Some(10)),
+                    (RegionKind::FrameBody("TPE1".to_string()), 34, 4, Some(24), Some(3)),
+                    (RegionKind::Padding, 38, 2, Some(27), Some(2)),
+                    (RegionKind::Audio, 40, 3, None, None)],
+               regions);
+
+    let regions = layout("./test-resources/v1-v2.mp3").unwrap();
+    assert_eq!(RegionKind::Header, regions[0].kind);
+    assert_eq!(RegionKind::FrameV1, regions[regions.len() - 1].kind);
+    assert_eq!(128, regions[regions.len() - 1].file_size);
+}
+
+#[test]
+fn metadata_visitor() {
+    let _ = env_logger::init();
+
+    struct TitleVisitor {
+        headers: usize,
+        skipped: Vec<(String, Vec<u8>)>,
+        titles: Vec<String>,
+        padding: usize,
+    }
+
+    impl TagVisitor for TitleVisitor {
+        fn on_header(&mut self, _: &Head) {
+            self.headers = self.headers + 1;
+        }
+
+        fn on_frame_header(&mut self, header: &FrameHeader, bytes: &[u8]) -> FrameAction {
+            if header.id() == id::TIT2 {
+                FrameAction::Decode
+            } else {
+                self.skipped.push((header.id(), bytes.to_vec()));
+                FrameAction::Skip
+            }
+        }
+
+        fn on_frame_body(&mut self, _: &FrameHeader, body: &FrameBody) {
+            if let &FrameBody::TIT2(ref frame) = body {
+                self.titles.push(frame.text.clone());
+            }
+        }
+
+        fn on_padding(&mut self, _: usize, size: usize, _: bool) {
+            self.padding = size;
+        }
+    }
+
+    let artist = build_text(4, "TPE1", "AB");
+    let bytes = build_tag(4, 0, &[&artist, &build_text(4, "TIT2", "CD"), &[0x00; 2]]);
+
+    let mut visitor = TitleVisitor {
+        headers: 0,
+        skipped: Vec::new(),
+        titles: Vec::new(),
+        padding: 0,
+    };
+    MetadataReader::from_bytes(&bytes).unwrap().visit(&mut visitor);
+
+    assert_eq!(1, visitor.headers);
+    assert_eq!(vec![("TPE1".to_string(), vec![0x00, 0x41, 0x42])], visitor.skipped);
+    assert_eq!(vec!["CD".to_string()], visitor.titles);
+    assert_eq!(2, visitor.padding);
+
+    // the decoded frames are same with the ones of the Iterator.
+    struct BodyVisitor {
+        bodies: Vec<String>,
+    }
+
+    impl TagVisitor for BodyVisitor {
+        fn on_frame_body(&mut self, header: &FrameHeader, body: &FrameBody) {
+            self.bodies.push(format!("{:?}", (header, body)));
+        }
+    }
+
+    for path in vec!["./test-resources/240.mp3", "./test-resources/v2.2.mp3"] {
+        let expected = MetadataReader::new(path)
+            .unwrap()
+            .filter_map(|unit| match unit {
+                Unit::FrameV2(header, body) => Some(format!("{:?}", (&header, &body))),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert!(expected.len() > 0);
+
+        let mut visitor = BodyVisitor { bodies: Vec::new() };
+        MetadataReader::new(path).unwrap().visit(&mut visitor);
+        assert_eq!(expected, visitor.bodies);
+    }
+}
+
+#[test]
+fn metadata_reader_send() {
+    let _ = env_logger::init();
+
+    let reader = MetadataReader::new("./test-resources/v1-v2.mp3").unwrap();
+    let units = std::thread::spawn(move || reader.collect::<Vec<_>>()).join().unwrap();
+    assert!(units.len() > 0);
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn metadata_async() {
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use futures::executor::block_on;
+    use futures::io::{AsyncRead, AsyncSeek};
+    use futures::io::Cursor as AsyncCursor;
+    use rtag::async_metadata::{AsyncMetadataReader, AsyncMetadataWriter};
+
+    //
+    // It count the bytes that are read.
+    //
+    struct Counted(AsyncCursor<Vec<u8>>, usize);
+
+    impl AsyncRead for Counted {
+        fn poll_read(mut self: Pin<&mut Self>,
+                     cx: &mut Context,
+                     buf: &mut [u8])
+                     -> Poll<io::Result<usize>> {
+            let poll = Pin::new(&mut self.0).poll_read(cx, buf);
+            if let Poll::Ready(Ok(n)) = poll {
+                self.1 = self.1 + n;
+            }
+            poll
+        }
+    }
+
+    impl AsyncSeek for Counted {
+        fn poll_seek(mut self: Pin<&mut Self>,
+                     cx: &mut Context,
+                     pos: io::SeekFrom)
+                     -> Poll<io::Result<u64>> {
+            Pin::new(&mut self.0).poll_seek(cx, pos)
+        }
+    }
+
+    let _ = env_logger::init();
+
+    let bytes = fs::read("./test-resources/v1-v2.mp3").unwrap();
+
+    let mut reader = AsyncMetadataReader::new(Counted(AsyncCursor::new(bytes.clone()), 0));
+    let units = block_on(reader.read()).unwrap().collect::<Vec<_>>();
+    let sync_units = MetadataReader::new("./test-resources/v1-v2.mp3")
+        .unwrap()
+        .collect::<Vec<_>>();
+    assert_eq!(format!("{:?}", sync_units), format!("{:?}", units));
+
+    // only the tag, the 10 bytes after it and the last 138 bytes are read, not the audio.
+    let info = probe("./test-resources/v1-v2.mp3").unwrap();
+    assert!(reader.into_inner().1 <= info.audio_offset + 10 + 138);
+
+    // the result is same with the one of MetadataWriter. the tag is rewritten to a smaller one
+    // with a clean write, to a larger one and to a smaller one without the frame1.
+    let tmp_dir = TempDir::new("rtag").unwrap();
+    let tmp_path = tmp_dir.path().join("v1-v2.mp3");
+    let path = tmp_path.to_str().unwrap();
+
+    let write = |units: Vec<Unit>, sync_units: Vec<Unit>, clean_write: bool| {
+        fs::copy("./test-resources/v1-v2.mp3", path).unwrap();
+        MetadataWriter::new(path).unwrap().write(sync_units, clean_write).unwrap();
+
+        let mut writer = AsyncMetadataWriter::new(AsyncCursor::new(bytes.clone()));
+        let len = block_on(writer.write(units, clean_write)).unwrap();
+
+        let mut written = writer.into_inner().into_inner();
+        written.truncate(len as usize);
+        assert_eq!(fs::read(path).unwrap(), written);
+    };
+
+    let read = || {
+        MetadataReader::new("./test-resources/v1-v2.mp3")
+            .unwrap()
+            .collect::<Vec<_>>()
+    };
+
+    write(units, sync_units, true);
+
+    let large = || {
+        let mut units = read();
+        units.push(Unit::FrameV2(FrameHeader::V24(FrameHeaderV4 {
+                                     id: "TIT3".to_string(),
+                                     size: 0,
+                                     status_flag: 0,
+                                     encoding_flag: 0,
+                                     data_length: None,
+                                     group_symbol: None,
+                                     encryption_method: None,
+                                 }),
+                                 FrameBody::TIT3(TEXT {
+                                     text_encoding: TextEncoding::ISO88591,
+                                     text: "A".repeat(20000),
+                                 })));
+        units
+    };
+    write(large(), large(), false);
+
+    let small = || read().into_iter().take(2).collect::<Vec<_>>();
+    write(small(), small(), false);
+
+    // the tag that a SEEK frame point to is fetched too.
+    let mut bytes = build_tag(4, 0, &[&build_frame(4, "SEEK", 0, &[0x00, 0x00, 0x00, 0x40])]);
+    bytes.extend_from_slice(&[0xff; 64]);
+    bytes.extend_from_slice(&build_tag(4, 0, &[&build_text(4, "TIT2", "CD")]));
+
+    let mut reader = AsyncMetadataReader::new(AsyncCursor::new(bytes.clone()));
+    let units = block_on(reader.read()).unwrap().collect::<Vec<_>>();
+    let sync_units = MetadataReader::from_bytes(&bytes).unwrap().collect::<Vec<_>>();
+    assert_eq!(4, units.len());
+    assert_eq!(format!("{:?}", sync_units), format!("{:?}", units));
+}
+
+#[test]
+fn metadata_borrowed_frames() {
+    use std::borrow::Cow;
+    use rtag::mmap;
+
+    let _ = env_logger::init();
+
+    let latin1 = build_frame(4, "TPE1", 0, &[0x00, 0x43, 0xe9]);
+    let bytes = build_tag(4, 0, &[&build_text(4, "TIT2", "AB"), &latin1, &[0x00; 2]]);
+
+    let options = ParseOptions::default();
+    let frames = mmap::frames(&bytes, &options).unwrap().map(|f| f.unwrap()).collect::<Vec<_>>();
+    assert_eq!(2, frames.len());
+    assert_eq!(23, frames[1].offset);
+
+    // the text in ASCII range borrows the slice.
+    match frames[0].text(&options).unwrap() {
+        Some(Cow::Borrowed(text)) => assert_eq!("AB", text),
+        _ => assert!(false),
+    }
+
+    // ISO-8859-1 out of ASCII range is decoded.
+    match frames[1].text(&options).unwrap() {
+        Some(Cow::Owned(text)) => assert_eq!("C\u{e9}", text),
+        _ => assert!(false),
+    }
+
+    match frames[1].decode(&options).unwrap() {
+        FrameBody::TPE1(frame) => assert_eq!("C\u{e9}", frame.text),
+        _ => assert!(false),
+    }
+
+    // the limits are same with the ones of MetadataReader.
+    let with_limits = |limits: Limits| ParseOptions { limits: limits, ..ParseOptions::default() };
+
+    let options = with_limits(Limits { max_tag_size: 27, ..Limits::default() });
+    let err = mmap::frames(&bytes, &options).err().unwrap();
+    assert_eq!(error::ErrorKind::LimitExceeded, err.kind());
+
+    let kinds = |limits: Limits| {
+        mmap::frames(&bytes, &with_limits(limits))
+            .unwrap()
+            .map(|f| f.map_err(|e| e.kind()).map(|f| f.header.id()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(vec![Err(error::ErrorKind::LimitExceeded)],
+               kinds(Limits { max_frame_size: 2, ..Limits::default() }));
+    assert_eq!(vec![Ok("TIT2".to_string()), Err(error::ErrorKind::LimitExceeded)],
+               kinds(Limits { max_frames: 1, ..Limits::default() }));
+
+    // the frame ids are same with the ones of MetadataReader.
+    let bytes = fs::read("./test-resources/v2.3-unsync.mp3").unwrap();
+    let ids = mmap::frames(&bytes, &ParseOptions::default())
+        .unwrap()
+        .map(|f| f.unwrap().header.id())
+        .collect::<Vec<_>>();
+    let reader_ids = MetadataReader::from_bytes(&bytes)
+        .unwrap()
+        .filter_map(|unit| match unit {
+            Unit::FrameV2(header, _) => Some(header.id()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(reader_ids, ids);
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn metadata_mapped_file() {
+    use rtag::mmap::MappedFile;
+
+    let _ = env_logger::init();
+
+    let file = MappedFile::open("./test-resources/240.mp3").unwrap();
+    let options = ParseOptions::default();
+    let titles = file.frames(&options)
+        .unwrap()
+        .map(|f| f.unwrap())
+        .filter(|f| f.header.id() == id::TIT2)
+        .map(|f| f.text(&options).unwrap().unwrap().into_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(1, titles.len());
+}
+
+#[test]
+fn metadata_limits() {
+    let _ = env_logger::init();
+
+    fn kinds(bytes: &[u8], limits: Limits) -> (usize, Vec<error::ErrorKind>) {
+        let mut reader = MetadataReader::from_bytes(bytes)
+            .unwrap()
+            .with_options(ParseOptions { limits: limits, ..ParseOptions::default() });
+        let results = reader.try_iter().collect::<Vec<_>>();
+        let frames = results.iter()
+            .filter(|r| match **r {
+                Ok(Unit::FrameV2(_, _)) => true,
+                _ => false,
+            })
+            .count();
+        let kinds = results.into_iter().filter_map(|r| r.err()).map(|e| e.kind()).collect();
+        (frames, kinds)
+    }
+
+    let bytes = build_tag(4, 0, &[&build_text(4, "TIT2", "AB"), &build_text(4, "TPE1", "CD")]);
+
+    assert_eq!((2, vec![]), kinds(&bytes, Limits::default()));
+    assert_eq!((0, vec![error::ErrorKind::LimitExceeded]),
+               kinds(&bytes, Limits { max_tag_size: 25, ..Limits::default() }));
+    assert_eq!((0, vec![error::ErrorKind::LimitExceeded]),
+               kinds(&bytes, Limits { max_frame_size: 2, ..Limits::default() }));
+    assert_eq!((1, vec![error::ErrorKind::LimitExceeded]),
+               kinds(&bytes, Limits { max_frames: 1, ..Limits::default() }));
+
+    // the decompressed size of 2.3 is checked before it is decompressed.
+    let compressed = build_frame(3, "TIT2", 0x0080, &[0x7f, 0xff, 0xff, 0xff, 0x78, 0x9c, 0x00]);
+    let bytes = build_tag(3, 0, &[&compressed]);
+
+    let mut reader = MetadataReader::from_bytes(&bytes)
+        .unwrap()
+        .with_options(ParseOptions {
+            lazy: true,
+            limits: Limits { max_decompressed_size: 1024, ..Limits::default() },
+            ..ParseOptions::default()
+        });
+    let options = ParseOptions {
+        limits: Limits { max_decompressed_size: 1024, ..Limits::default() },
+        ..ParseOptions::default()
+    };
+    match reader.nth(1) {
+        Some(Unit::LazyFrameV2(frame)) => {
+            let err = frame.decode_with(&options).unwrap_err();
+            assert_eq!(error::ErrorKind::LimitExceeded, err.kind());
+            assert_eq!(Some("TIT2"), err.frame_id());
+
+            // the frame keeps the limits that it is read with.
+            let err = frame.decode().unwrap_err();
+            assert_eq!(error::ErrorKind::LimitExceeded, err.kind());
+        }
+        _ => assert!(false),
+    }
+
+    let options = ParseOptions {
+        limits: Limits { max_tag_size: 16, ..Limits::default() },
+        ..ParseOptions::default()
+    };
+    let err = layout_reader_with(&mut Cursor::new(bytes), &options).unwrap_err();
+    assert_eq!(error::ErrorKind::LimitExceeded, err.kind());
+}
+
+#[test]
+fn metadata_data_length() {
+    let _ = env_logger::init();
+
+    fn tag(data_length: u8) -> Vec<u8> {
+        let body = [0x00, 0x00, 0x00, data_length, 0x00, 0x41, 0x42];
+        build_tag(4, 0, &[&build_frame(4, "TIT2", 0x0001, &body)])
+    }
+
+    let units = MetadataReader::from_bytes(&tag(3))
+        .unwrap()
+        .try_iter()
+        .collect::<Vec<_>>();
+    match units[1] {
+        Ok(Unit::FrameV2(ref header, FrameBody::TIT2(ref frame))) => {
+            assert_eq!(Some(3), header.data_length());
+            assert_eq!("AB", frame.text);
+        }
+        _ => assert!(false),
+    }
+
+    let units = MetadataReader::from_bytes(&tag(5))
+        .unwrap()
+        .try_iter()
+        .collect::<Vec<_>>();
+    match units[1] {
+        Err(ref e) => {
+            assert_eq!(error::ErrorKind::DataLength, e.kind());
+            assert_eq!(Some("TIT2"), e.frame_id());
+        }
+        _ => assert!(false),
+    }
+
+    //
+    // The writer set the data length indicator to the size of the body.
+    //
+    let mut header = FrameHeaderV4 {
+        id: "TIT2".to_string(),
+        size: 0,
+        status_flag: 0,
+        encoding_flag: 0,
+        data_length: None,
+        group_symbol: None,
+        encryption_method: None,
+    };
+    header.set_flag(FrameHeaderFlag::DataLength);
+    let body = FrameBody::TIT2(TEXT {
+        text_encoding: TextEncoding::ISO88591,
+        text: "AB".to_string(),
+    });
+    let bytes = MetadataWriter::new("").unwrap().frame4(&mut header, body).unwrap();
+
+    assert_eq!(Some(3), header.data_length);
+    assert_eq!(&[0x00, 0x00, 0x00, 0x03], &bytes[10..14]);
+}
+
+#[test]
+fn metadata_group_encryption() {
+    let _ = env_logger::init();
+
+    fn first_frame(bytes: &[u8]) -> (FrameHeader, FrameBody) {
+        match MetadataReader::from_bytes(bytes).unwrap().nth(1) {
+            Some(Unit::FrameV2(header, body)) => (header, body),
+            _ => panic!("no frame"),
+        }
+    }
+
+    let frame = build_frame(3, "TIT2", 0x0020, &[0x81, 0x00, 0x41, 0x42]);
+    let bytes = build_tag(3, 0, &[&frame]);
+
+    let (header, body) = first_frame(&bytes);
+    assert_eq!(Some(0x81), header.group_symbol());
+    assert_eq!(None, header.encryption_method());
+
+    let mut header = match header {
+        FrameHeader::V23(header) => header,
+        _ => panic!("not 2.3"),
+    };
+    assert_eq!(frame, MetadataWriter::new("").unwrap().frame3(&mut header, body).unwrap());
+
+    let frame = build_frame(4, "TIT2", 0x0044, &[0x81, 0x82, 0x01, 0x02, 0x03]);
+    let bytes = build_tag(4, 0, &[&frame]);
+
+    let (header, body) = first_frame(&bytes);
+    assert_eq!(Some(0x81), header.group_symbol());
+    assert_eq!(Some(0x82), header.encryption_method());
+
+    let data = match body {
+        FrameBody::SKIP(_, data) => data,
+        _ => panic!("not encrypted"),
+    };
+    assert_eq!(vec![0x01, 0x02, 0x03], data);
+
+    let mut header = match header {
+        FrameHeader::V24(header) => header,
+        _ => panic!("not 2.4"),
+    };
+    let body = FrameBody::OBJECT(OBJECT { data: data });
+    assert_eq!(frame, MetadataWriter::new("").unwrap().frame4(&mut header, body).unwrap());
+}
+
+#[test]
+fn metadata_decryptor() {
+    let _ = env_logger::init();
+
+    struct Xor(u8);
+
+    impl FrameDecryptor for Xor {
+        fn decrypt(&self, _: &ENCR, _: &FrameHeader, bytes: &[u8]) -> error::Result<Vec<u8>> {
+            Ok(bytes.iter().map(|b| b ^ self.0).collect())
+        }
+    }
+
+    //
+    // TIT2 is encrypted with the method of the ENCR frame after it. TPE1 is encrypted with
+    // an unknown method.
+    //
+    let bytes = build_tag(4,
+                          0,
+                          &[&build_frame(4, "TIT2", 0x0004, &[0x80, 0xff, 0xbe, 0xbd]),
+                            &build_frame(4, "ENCR", 0, &[0x6b, 0x00, 0x80]),
+                            &build_frame(4, "TPE1", 0x0004, &[0x81, 0xff, 0xbe, 0xbd])]);
+
+    let keys = vec![DecryptorKey::Owner("k".to_string()), DecryptorKey::Method(0x80)];
+
+    for key in keys {
+        let mut reader = MetadataReader::from_bytes(&bytes)
+            .unwrap()
+            .with_decryptor(key, Xor(0xff));
+        let bodies = reader.try_iter()
+            .filter_map(|unit| match unit {
+                Ok(Unit::FrameV2(_, body)) => Some(body),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        match bodies[0] {
+            FrameBody::TIT2(ref frame) => assert_eq!("AB", frame.text),
+            _ => assert!(false),
+        }
+        match bodies[2] {
+            FrameBody::SKIP(ref id, ref data) => {
+                assert_eq!("TPE1", id);
+                assert_eq!(&vec![0xff, 0xbe, 0xbd], data);
+            }
+            _ => assert!(false),
+        }
+    }
+
+    let mut reader = MetadataReader::from_bytes(&bytes)
+        .unwrap()
+        .with_decryptor(DecryptorKey::Owner("other".to_string()), Xor(0xff));
+    match reader.nth(1) {
+        Some(Unit::FrameV2(_, FrameBody::SKIP(ref id, _))) => assert_eq!("TIT2", id),
+        _ => assert!(false),
+    }
+}
+
+#[test]
+fn metadata_round_trip() {
+    let _ = env_logger::init();
+
+    //
+    // The tag is unsynchronised. TIT2 is UTF-16 with the terminator, XABC is unknown,
+    // TPE1 has a group symbol and there is padding.
+    //
+    // the sizes of TIT2 and XABC are before 0x00 is inserted after 0xff.
+    let title = [0x54, 0x49, 0x54, 0x32, 0x00, 0x00, 0x00, 0x07, 0x00, 0x00, 0x01, 0xff, 0x00, 0xfe,
+                 0x41, 0x00, 0x00, 0x00];
+    let unknown = [0x58, 0x41, 0x42, 0x43, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0xff, 0x00, 0x00,
+                   0x01];
+    let grouped = build_frame(3, "TPE1", 0x0020, &[0x05, 0x00, 0x41, 0x42]);
+    let mut bytes = build_tag(3, 0x80, &[&title, &unknown, &grouped, &[0x00; 6]]);
+    bytes.extend_from_slice(&[0xaa; 4]);
+
+    let units = MetadataReader::from_bytes(&bytes)
+        .unwrap()
+        .with_options(ParseOptions { lazy: true, ..ParseOptions::default() })
+        .collect::<Vec<_>>();
+
+    match units[2] {
+        Unit::LazyFrameV2(ref frame) => {
+            match frame.decode().unwrap() {
+                FrameBody::Unknown { ref id, ref raw } => {
+                    assert_eq!("XABC", id);
+                    assert_eq!(&vec![0xff, 0x00, 0x01], raw);
+                }
+                _ => assert!(false),
+            }
+        }
+        _ => assert!(false),
+    }
+
+    let writer = MetadataWriter::new("").unwrap().with_round_trip(true);
+    assert_eq!(bytes, writer.to_file_bytes(units, false, &bytes).unwrap());
+
+    // the padding is not zero.
+    let bytes = fs::read("./test-resources/v2.3-encrypted-frame.mp3").unwrap();
+    let units = MetadataReader::from_bytes(&bytes)
+        .unwrap()
+        .with_options(ParseOptions { lazy: true, ..ParseOptions::default() })
+        .collect::<Vec<_>>();
+    assert!(units.iter().any(|unit| match unit {
+        &Unit::Padding { all_zero, .. } => !all_zero,
+        _ => false,
+    }));
+    assert_eq!(bytes, writer.to_file_bytes(units, false, &bytes).unwrap());
+}
+
+#[test]
+fn metadata_not_synchsafe() {
+    let _ = env_logger::init();
+
+    //
+    // The size of TIT2 is 200 that is written as a plain 32-bit integer.
+    //
+    let mut plain = vec![0x54, 0x49, 0x54, 0x32, 0x00, 0x00, 0x00, 0xc8, 0x00, 0x00, 0x00];
+    plain.extend_from_slice(&[0x41; 199]);
+    let bytes = build_tag(4, 0, &[&plain, &build_text(4, "TPE1", "CD")]);
+
+    let mut reader = MetadataReader::from_bytes(&bytes).unwrap();
+    let units = reader.try_iter().collect::<Vec<_>>();
+
+    assert_eq!(3, units.len());
+    match units[1] {
+        Ok(Unit::FrameV2(_, FrameBody::TIT2(ref frame))) => assert_eq!(199, frame.text.len()),
+        _ => assert!(false),
+    }
+    match units[2] {
+        Ok(Unit::FrameV2(_, FrameBody::TPE1(ref frame))) => assert_eq!("CD", frame.text),
+        _ => assert!(false),
+    }
+
+    let warnings = reader.diagnostics()
+        .iter()
+        .map(|d| (d.severity, d.kind, d.offset, d.frame_id.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(vec![(error::Severity::Warning,
+                     error::ErrorKind::NotSynchsafe,
+                     Some(10),
+                     Some("TIT2".to_string()))],
+               warnings);
+
+    let sizes = rtag::mmap::frames(&bytes, &ParseOptions::default())
+        .unwrap()
+        .map(|frame| frame.unwrap().header.size())
+        .collect::<Vec<_>>();
+    assert_eq!(vec![200, 3], sizes);
+
+    // the plain size is written back as it is read in round-trip mode.
+    let plain_sizes = rtag::mmap::frames(&bytes, &ParseOptions::default())
+        .unwrap()
+        .map(|frame| frame.unwrap().to_lazy().plain_size)
+        .collect::<Vec<_>>();
+    assert_eq!(vec![true, false], plain_sizes);
+
+    let units = MetadataReader::from_bytes(&bytes)
+        .unwrap()
+        .with_options(ParseOptions { lazy: true, ..ParseOptions::default() })
+        .collect::<Vec<_>>();
+    let writer = MetadataWriter::new("").unwrap().with_round_trip(true);
+    assert_eq!(bytes, writer.to_file_bytes(units, false, &bytes).unwrap());
+}
+
+#[test]
+fn metadata_fix_units_v22_ids() {
+    let _ = env_logger::init();
+
+    assert_eq!("TIT2", frame2_to_frame4("TT2"));
+    assert_eq!("TALB", frame2_to_frame4("TAL"));
+    assert_eq!("COMM", frame2_to_frame4("COM"));
+    assert_eq!("UNKNOWN", frame2_to_frame4("ZZZ"));
+
+    let path = "./test-resources/v2.2.mp3";
+    let units = MetadataReader::new(path).unwrap().collect::<Vec<Unit>>();
+    let units = MetadataWriter::new(path).unwrap().fix_units(&units).unwrap();
+
+    let ids = units.iter()
+        .filter_map(|unit| match unit {
+            &Unit::FrameV2(ref header, ref body) => Some((header.id(), framebody_to_id(body, 4))),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert!(ids.len() > 0);
+
+    for (header_id, body_id) in ids {
+        assert_eq!(body_id, header_id);
+    }
+}
+
+#[test]
+fn metadata_no_tag() {
+    let _ = env_logger::init();
+
+    let path = "./test-resources/230-no-id3.mp3";
+    let mut reader = MetadataReader::new(path).unwrap();
+    assert_eq!(0, reader.by_ref().count());
+    assert_eq!(0, reader.diagnostics().len());
+
+    let bytes = fs::read(path).unwrap();
+    let results = MetadataReader::from_bytes(&bytes).unwrap().try_iter().collect::<Vec<_>>();
+    assert_eq!(0, results.len());
+
+    // a new tag is written before the audio, and the audio is kept as it is.
+    let units = vec![Unit::FrameV2(FrameHeader::V24(FrameHeaderV4 {
+                                       id: "TIT2".to_string(),
+                                       size: 0,
+                                       status_flag: 0,
+                                       encoding_flag: 0,
+                                       data_length: None,
+                                       group_symbol: None,
+                                       encryption_method: None,
+                                   }),
+                                   FrameBody::TIT2(TEXT {
+                                       text_encoding: TextEncoding::ISO88591,
+                                       text: "AB".to_string(),
+                                   }))];
+    let written = MetadataWriter::new("").unwrap().to_file_bytes(units, false, &bytes).unwrap();
+    assert_eq!(10 + 13 + bytes.len(), written.len());
+    assert_eq!(&bytes[..], &written[23..]);
+}
+
+#[test]
+fn metadata_layout_broken() {
+    let _ = env_logger::init();
+
+    // the tag size and the frame size overrun the file.
+    let overrun = [0x54, 0x49, 0x54, 0x32, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x41, 0x42];
+    let mut bytes = build_tag(3, 0, &[&overrun]);
+    bytes[9] = 0x14;
+
+    let regions = layout_reader(&mut Cursor::new(bytes))
+        .unwrap()
+        .into_iter()
+        .map(|r| (r.kind, r.file_offset, r.file_size, r.tag_offset, r.tag_size))
+        .collect::<Vec<_>>();
+
+    assert_eq!(vec![(RegionKind::Header, 0, 10, None, None),
+                    (RegionKind::FrameHeader("TIT2".to_string()), 10, 10, Some(0), Some(10)),
+                    (RegionKind::FrameBody("TIT2".to_string()), 20, 3, Some(10), Some(3)),
+                    (RegionKind::Overrun, 23, 5, Some(13), Some(5)),
+                    (RegionKind::Overrun, 23, 7, None, None)],
+               regions);
+
+    // the SEEK frame point to the second tag, and the third tag is located by the footer.
+    let mut bytes = build_tag(4, 0, &[&build_frame(4, "SEEK", 0, &[0x00, 0x00, 0x00, 0x04])]);
+    bytes.extend_from_slice(&[0xff, 0xfb, 0x90, 0x00]);
+    bytes.extend_from_slice(&build_tag(4, 0, &[&build_text(4, "TIT2", "CD")]));
+    bytes.extend_from_slice(&[0xff, 0xfb, 0x90, 0x00]);
+    bytes.extend_from_slice(&build_tag(4, 0x10, &[&build_text(4, "TIT2", "AB")]));
+    bytes.extend_from_slice(&build_frame1());
+
+    let regions = layout_reader(&mut Cursor::new(bytes))
+        .unwrap()
+        .into_iter()
+        .map(|r| (r.kind, r.file_offset, r.file_size))
+        .collect::<Vec<_>>();
+
+    assert_eq!(vec![(RegionKind::Header, 0, 10),
+                    (RegionKind::FrameHeader("SEEK".to_string()), 10, 10),
+                    (RegionKind::FrameBody("SEEK".to_string()), 20, 4),
+                    (RegionKind::Audio, 24, 4),
+                    (RegionKind::Header, 28, 10),
+                    (RegionKind::FrameHeader("TIT2".to_string()), 38, 10),
+                    (RegionKind::FrameBody("TIT2".to_string()), 48, 3),
+                    (RegionKind::Audio, 51, 4),
+                    (RegionKind::Header, 55, 10),
+                    (RegionKind::FrameHeader("TIT2".to_string()), 65, 10),
+                    (RegionKind::FrameBody("TIT2".to_string()), 75, 3),
+                    (RegionKind::Footer, 78, 10),
+                    (RegionKind::FrameV1, 88, 128)],
+               regions);
+}
+
 macro_rules! define_compare_frame {
     (
         $( $id:ident ),*